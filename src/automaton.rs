@@ -0,0 +1,443 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    hash::Hash,
+};
+
+use crate::program::Stimulus;
+
+#[derive(Debug, Clone)]
+pub struct Dfa<State, Alphabet>
+where
+    State: Hash + Eq,
+    Alphabet: Hash + Eq,
+{
+    initial: State,
+    accepting: HashSet<State>,
+    table: HashMap<State, HashMap<Alphabet, State>>,
+}
+
+impl<State, Alphabet> Dfa<State, Alphabet>
+where
+    State: Hash + Eq,
+    Alphabet: Hash + Eq,
+{
+    /// A missing transition rejects straight away.
+    pub fn accepts<I>(&self, input: I) -> bool
+    where
+        I: IntoIterator<Item = Alphabet>,
+    {
+        let mut state = &self.initial;
+        for symbol in input {
+            match self.table.get(state).and_then(|sub| sub.get(&symbol)) {
+                Some(next) => state = next,
+                None => return false,
+            }
+        }
+        self.accepting.contains(state)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DfaBuilder<State, Alphabet>
+where
+    State: Hash + Eq,
+    Alphabet: Hash + Eq,
+{
+    accepting: HashSet<State>,
+    table: HashMap<State, HashMap<Alphabet, State>>,
+}
+
+impl<State, Alphabet> Default for DfaBuilder<State, Alphabet>
+where
+    State: Hash + Eq,
+    Alphabet: Hash + Eq,
+{
+    fn default() -> Self {
+        Self {
+            accepting: HashSet::new(),
+            table: HashMap::new(),
+        }
+    }
+}
+
+impl<State, Alphabet> DfaBuilder<State, Alphabet>
+where
+    State: Hash + Eq,
+    Alphabet: Hash + Eq,
+{
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn build(self, initial: State) -> Dfa<State, Alphabet> {
+        Dfa {
+            initial,
+            accepting: self.accepting,
+            table: self.table,
+        }
+    }
+
+    pub fn add_accepting(&mut self, state: State) {
+        self.accepting.insert(state);
+    }
+
+    pub fn with_accepting(mut self, state: State) -> Self {
+        self.add_accepting(state);
+        self
+    }
+
+    pub fn add_transition<S>(&mut self, stimulus: S, next: State)
+    where
+        Stimulus<State, Alphabet>: From<S>,
+    {
+        let stimulus: Stimulus<_, _> = stimulus.into();
+        self.table
+            .entry(stimulus.state)
+            .or_default()
+            .insert(stimulus.read, next);
+    }
+
+    pub fn with_transition<S>(mut self, stimulus: S, next: State) -> Self
+    where
+        Stimulus<State, Alphabet>: From<S>,
+    {
+        self.add_transition(stimulus, next);
+        self
+    }
+}
+
+/// Reading `None` is an epsilon move.
+#[derive(Debug, Clone)]
+pub struct Nfa<State, Alphabet>
+where
+    State: Hash + Eq,
+    Alphabet: Hash + Eq,
+{
+    initial: State,
+    accepting: HashSet<State>,
+    table: HashMap<State, HashMap<Option<Alphabet>, Vec<State>>>,
+}
+
+impl<State, Alphabet> Nfa<State, Alphabet>
+where
+    State: Hash + Eq + Clone,
+    Alphabet: Hash + Eq,
+{
+    pub fn accepts<I>(&self, input: I) -> bool
+    where
+        I: IntoIterator<Item = Alphabet>,
+    {
+        let mut current = self.closure(vec![self.initial.clone()]);
+        for symbol in input {
+            let symbol = Some(symbol);
+            let next = current
+                .iter()
+                .filter_map(|state| self.table.get(state).and_then(|sub| sub.get(&symbol)))
+                .flatten()
+                .cloned()
+                .collect();
+            current = self.closure(next);
+            if current.is_empty() {
+                return false;
+            }
+        }
+        current.iter().any(|state| self.accepting.contains(state))
+    }
+
+    /// Every state reachable from `states` by epsilon moves alone.
+    fn closure(&self, states: Vec<State>) -> HashSet<State> {
+        let mut seen = HashSet::new();
+        let mut todo = states;
+        while let Some(state) = todo.pop() {
+            if let Some(next) = self.table.get(&state).and_then(|sub| sub.get(&None)) {
+                todo.extend(next.iter().filter(|s| !seen.contains(*s)).cloned());
+            }
+            seen.insert(state);
+        }
+        seen
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NfaBuilder<State, Alphabet>
+where
+    State: Hash + Eq,
+    Alphabet: Hash + Eq,
+{
+    accepting: HashSet<State>,
+    table: HashMap<State, HashMap<Option<Alphabet>, Vec<State>>>,
+}
+
+impl<State, Alphabet> Default for NfaBuilder<State, Alphabet>
+where
+    State: Hash + Eq,
+    Alphabet: Hash + Eq,
+{
+    fn default() -> Self {
+        Self {
+            accepting: HashSet::new(),
+            table: HashMap::new(),
+        }
+    }
+}
+
+impl<State, Alphabet> NfaBuilder<State, Alphabet>
+where
+    State: Hash + Eq,
+    Alphabet: Hash + Eq,
+{
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn build(self, initial: State) -> Nfa<State, Alphabet> {
+        Nfa {
+            initial,
+            accepting: self.accepting,
+            table: self.table,
+        }
+    }
+
+    pub fn add_accepting(&mut self, state: State) {
+        self.accepting.insert(state);
+    }
+
+    pub fn with_accepting(mut self, state: State) -> Self {
+        self.add_accepting(state);
+        self
+    }
+
+    /// Adds one more possible successor, keeping any that were already there.
+    pub fn add_transition<S>(&mut self, stimulus: S, next: State)
+    where
+        Stimulus<State, Option<Alphabet>>: From<S>,
+    {
+        let stimulus: Stimulus<_, _> = stimulus.into();
+        self.table
+            .entry(stimulus.state)
+            .or_default()
+            .entry(stimulus.read)
+            .or_default()
+            .push(next);
+    }
+
+    pub fn with_transition<S>(mut self, stimulus: S, next: State) -> Self
+    where
+        Stimulus<State, Option<Alphabet>>: From<S>,
+    {
+        self.add_transition(stimulus, next);
+        self
+    }
+}
+
+/// What a pushdown automaton reads: an input symbol and the top of the stack.
+/// `None` in either position consumes nothing from it.
+pub type PdaRead<Alphabet> = (Option<Alphabet>, Option<Alphabet>);
+
+/// The symbols to push are listed top first.
+pub type PdaMove<State, Alphabet> = (State, Vec<Alphabet>);
+
+type PdaTable<State, Alphabet> =
+    HashMap<State, HashMap<PdaRead<Alphabet>, Vec<PdaMove<State, Alphabet>>>>;
+
+/// A nondeterministic pushdown automaton accepting by final state.
+#[derive(Debug, Clone)]
+pub struct Pda<State, Alphabet>
+where
+    State: Hash + Eq,
+    Alphabet: Hash + Eq,
+{
+    initial: State,
+    accepting: HashSet<State>,
+    table: PdaTable<State, Alphabet>,
+}
+
+impl<State, Alphabet> Pda<State, Alphabet>
+where
+    State: Hash + Eq + Clone,
+    Alphabet: Hash + Eq + Clone,
+{
+    /// Searches the configurations breadth first.
+    /// Epsilon pushes can make that search endless, so it gives up with `None` after visiting
+    /// `limit` configurations.
+    pub fn accepts<I>(&self, input: I, limit: usize) -> Option<bool>
+    where
+        I: IntoIterator<Item = Alphabet>,
+    {
+        let input = input.into_iter().collect::<Vec<_>>();
+        let start = (self.initial.clone(), 0, Vec::<Alphabet>::new());
+        let mut seen = HashSet::new();
+        let mut todo = VecDeque::from(vec![start]);
+        while let Some((state, pos, stack)) = todo.pop_front() {
+            if pos == input.len() && self.accepting.contains(&state) {
+                return Some(true);
+            }
+            if seen.len() >= limit {
+                return None;
+            }
+            if !seen.insert((state.clone(), pos, stack.clone())) {
+                continue;
+            }
+            let subtable = match self.table.get(&state) {
+                Some(subtable) => subtable,
+                None => continue,
+            };
+            let reads = input
+                .get(pos)
+                .map(|symbol| (Some(symbol.clone()), pos + 1))
+                .into_iter()
+                .chain(Some((None, pos)));
+            for (read, next_pos) in reads {
+                let pops = stack
+                    .last()
+                    .map(|top| (Some(top.clone()), stack.len() - 1))
+                    .into_iter()
+                    .chain(Some((None, stack.len())));
+                for (pop, keep) in pops {
+                    let moves = match subtable.get(&(read.clone(), pop)) {
+                        Some(moves) => moves,
+                        None => continue,
+                    };
+                    for (next, push) in moves {
+                        let mut stack = stack[..keep].to_vec();
+                        stack.extend(push.iter().rev().cloned());
+                        todo.push_back((next.clone(), next_pos, stack));
+                    }
+                }
+            }
+        }
+        Some(false)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PdaBuilder<State, Alphabet>
+where
+    State: Hash + Eq,
+    Alphabet: Hash + Eq,
+{
+    accepting: HashSet<State>,
+    table: PdaTable<State, Alphabet>,
+}
+
+impl<State, Alphabet> Default for PdaBuilder<State, Alphabet>
+where
+    State: Hash + Eq,
+    Alphabet: Hash + Eq,
+{
+    fn default() -> Self {
+        Self {
+            accepting: HashSet::new(),
+            table: HashMap::new(),
+        }
+    }
+}
+
+impl<State, Alphabet> PdaBuilder<State, Alphabet>
+where
+    State: Hash + Eq,
+    Alphabet: Hash + Eq,
+{
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn build(self, initial: State) -> Pda<State, Alphabet> {
+        Pda {
+            initial,
+            accepting: self.accepting,
+            table: self.table,
+        }
+    }
+
+    pub fn add_accepting(&mut self, state: State) {
+        self.accepting.insert(state);
+    }
+
+    pub fn with_accepting(mut self, state: State) -> Self {
+        self.add_accepting(state);
+        self
+    }
+
+    /// Adds one more possible move, keeping any that were already there.
+    pub fn add_transition<S>(&mut self, stimulus: S, mv: PdaMove<State, Alphabet>)
+    where
+        Stimulus<State, PdaRead<Alphabet>>: From<S>,
+    {
+        let stimulus: Stimulus<_, _> = stimulus.into();
+        self.table
+            .entry(stimulus.state)
+            .or_default()
+            .entry(stimulus.read)
+            .or_default()
+            .push(mv);
+    }
+
+    pub fn with_transition<S>(mut self, stimulus: S, mv: PdaMove<State, Alphabet>) -> Self
+    where
+        Stimulus<State, PdaRead<Alphabet>>: From<S>,
+    {
+        self.add_transition(stimulus, mv);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dfa_even_zeros() {
+        let dfa = DfaBuilder::new()
+            .with_transition(("even", 0), "odd")
+            .with_transition(("even", 1), "even")
+            .with_transition(("odd", 0), "even")
+            .with_transition(("odd", 1), "odd")
+            .with_accepting("even")
+            .build("even");
+        assert!(dfa.accepts(vec![]));
+        assert!(dfa.accepts(vec![0, 1, 0]));
+        assert!(!dfa.accepts(vec![0, 1, 1]));
+        assert!(!dfa.accepts(vec![2]));
+    }
+
+    #[test]
+    fn nfa_ends_in_ab() {
+        let nfa = NfaBuilder::new()
+            .with_transition(("start", Some('a')), "start")
+            .with_transition(("start", Some('b')), "start")
+            .with_transition(("start", None), "guess")
+            .with_transition(("guess", Some('a')), "a")
+            .with_transition(("a", Some('b')), "ab")
+            .with_accepting("ab")
+            .build("start");
+        assert!(nfa.accepts("ab".chars()));
+        assert!(nfa.accepts("babab".chars()));
+        assert!(!nfa.accepts("aba".chars()));
+        assert!(!nfa.accepts("".chars()));
+    }
+
+    #[test]
+    fn pda_matched_brackets() {
+        let pda = PdaBuilder::new()
+            .with_transition(("start", (None, None)), ("open", vec!['$']))
+            .with_transition(("open", (Some('a'), None)), ("open", vec!['a']))
+            .with_transition(("open", (None, None)), ("close", vec![]))
+            .with_transition(("close", (Some('b'), Some('a'))), ("close", vec![]))
+            .with_transition(("close", (None, Some('$'))), ("done", vec![]))
+            .with_accepting("done")
+            .build("start");
+        assert_eq!(pda.accepts("".chars(), 1000), Some(true));
+        assert_eq!(pda.accepts("aaabbb".chars(), 1000), Some(true));
+        assert_eq!(pda.accepts("aabbb".chars(), 1000), Some(false));
+        assert_eq!(pda.accepts("abab".chars(), 1000), Some(false));
+    }
+
+    #[test]
+    fn pda_gives_up() {
+        let pda = PdaBuilder::new()
+            .with_transition(("grow", (None, None)), ("grow", vec!['x']))
+            .build("grow");
+        assert_eq!(pda.accepts("a".chars(), 1000), None);
+    }
+}
@@ -0,0 +1,204 @@
+use std::{
+    fmt,
+    io::{self, Read, Seek, SeekFrom},
+};
+
+use crate::{
+    busy_beaver,
+    program::{Goto, Movement, ProgramBuilder},
+    tape::Unbounded,
+    turing_machine::TuringMachine,
+};
+
+/// Bytes before the first machine, holding counts we don't need.
+pub const HEADER_LEN: u64 = 30;
+
+/// Bytes per machine: five states reading two symbols, three bytes per transition.
+pub const MACHINE_LEN: u64 = 30;
+
+const STATES: usize = 5;
+const SYMBOLS: usize = 2;
+
+/// A bbchallenge seed database, such as `all_5_states_undecided_machines_with_global_header`.
+///
+/// Each transition is stored as a byte to write, a byte for the move (0 right, 1 left) and
+/// a byte for the next state (1 for `A` through 5 for `E`), with 0 for an undefined transition,
+/// which halts as `---` does in busy beaver notation.
+#[derive(Debug)]
+pub struct Database<R> {
+    rdr: R,
+    len: u64,
+}
+
+/// How running a machine with a step limit turned out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// Reached a transition the machine doesn't define.
+    Halted,
+    /// Still running when the step limit ran out.
+    Undecided,
+}
+
+impl<R: Read + Seek> Database<R> {
+    pub fn new(mut rdr: R) -> io::Result<Self> {
+        let size = rdr.seek(SeekFrom::End(0))?;
+        if size < HEADER_LEN || !(size - HEADER_LEN).is_multiple_of(MACHINE_LEN) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "A database is a {}-byte header and {} bytes per machine, not {} bytes.",
+                    HEADER_LEN, MACHINE_LEN, size
+                ),
+            ));
+        }
+        Ok(Self {
+            rdr,
+            len: (size - HEADER_LEN) / MACHINE_LEN,
+        })
+    }
+
+    /// How many machines the database holds.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The machine at `index`, numbered from 0, starting in `busy_beaver::INITIAL`.
+    pub fn get(&mut self, index: u64) -> io::Result<ProgramBuilder<usize, usize>> {
+        if index >= self.len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Machine {} is out of range; the database has {}.",
+                    index, self.len
+                ),
+            ));
+        }
+        let mut record = [0; MACHINE_LEN as usize];
+        self.rdr
+            .seek(SeekFrom::Start(HEADER_LEN + index * MACHINE_LEN))?;
+        self.rdr.read_exact(&mut record)?;
+        decode(&record).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Machine {} is malformed: {:?}", index, &record[..]),
+            )
+        })
+    }
+}
+
+fn decode(record: &[u8]) -> Option<ProgramBuilder<usize, usize>> {
+    let mut builder = ProgramBuilder::new();
+    for (i, transition) in record.chunks(3).enumerate() {
+        let (state, read) = (i / SYMBOLS, i % SYMBOLS);
+        let (write, mv, goto) = (transition[0], transition[1], transition[2]);
+        if goto == 0 {
+            builder.add_transition((state, read), busy_beaver::undefined(read));
+            continue;
+        }
+        if write as usize >= SYMBOLS || goto as usize > STATES {
+            return None;
+        }
+        let mv = match mv {
+            0 => Movement::Right,
+            1 => Movement::Left,
+            _ => return None,
+        };
+        let goto = Goto::Run(goto as usize - 1);
+        builder.add_transition((state, read), (goto, write as usize, Some(mv)));
+    }
+    Some(builder)
+}
+
+/// Runs a machine on a blank tape for up to `limit` steps, returning how it ended and the
+/// number of steps it took.
+pub fn decide(builder: ProgramBuilder<usize, usize>, limit: u64) -> (Outcome, u64) {
+    let prog = builder.build(busy_beaver::INITIAL);
+    let tape = Unbounded::with_blank(busy_beaver::BLANK);
+    let mut machine = TuringMachine::new(prog, tape);
+    let mut steps = 0;
+    loop {
+        match machine.step() {
+            // Nothing rejects, since undefined transitions halt.
+            Some(_) => return (Outcome::Halted, steps),
+            None if steps == limit => return (Outcome::Undecided, steps),
+            None => steps += 1,
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Outcome::Halted => "halted",
+            Outcome::Undecided => "undecided",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// The bytes of a machine written in busy beaver notation, with `---` left as zeros.
+    fn encode(code: &str) -> Vec<u8> {
+        let code = code.replace('_', "");
+        code.as_bytes()
+            .chunks(3)
+            .flat_map(|t| match t {
+                b"---" => vec![0, 0, 0],
+                [write, mv, goto] => vec![write - b'0', (*mv == b'L') as u8, goto - b'A' + 1],
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    const MACHINES: [&str; 2] = [
+        "1RB---_1LC0RB_1LD1LC_1RE0LE_1LA---",
+        "1RB1LC_1RC1RB_1RD0LE_1LA1LD_---0LA",
+    ];
+
+    fn database() -> Database<Cursor<Vec<u8>>> {
+        let mut bytes = vec![0; HEADER_LEN as usize];
+        for machine in &MACHINES {
+            bytes.extend(encode(machine));
+        }
+        Database::new(Cursor::new(bytes)).expect("Well-formed database.")
+    }
+
+    #[test]
+    fn reads_by_index() {
+        let mut db = database();
+        assert_eq!(db.len(), 2);
+        for (index, machine) in MACHINES.iter().enumerate() {
+            let builder = db.get(index as u64).expect("In range.");
+            assert_eq!(busy_beaver::emit(&builder).as_deref(), Ok(*machine));
+        }
+        assert!(db.get(2).is_err());
+    }
+
+    #[test]
+    fn truncated_database() {
+        let bytes = vec![0; HEADER_LEN as usize + 29];
+        assert!(Database::new(Cursor::new(bytes)).is_err());
+    }
+
+    #[test]
+    fn decides_within_the_limit() {
+        // The two-state champion, with its halting transition left undefined. Reaching that
+        // transition halts, after the same number of steps.
+        let bb2 = || decode(&encode("1RB1LB_1LA---_------_------_------")).expect("Valid.");
+        assert_eq!(decide(bb2(), 100), (Outcome::Halted, 6));
+        assert_eq!(decide(bb2(), 3), (Outcome::Undecided, 3));
+        let mut db = database();
+        assert_eq!(
+            decide(db.get(1).expect("In range."), 1000),
+            (Outcome::Undecided, 1000)
+        );
+    }
+}
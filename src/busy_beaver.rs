@@ -0,0 +1,224 @@
+use std::{collections::HashMap, error::Error, fmt};
+
+use crate::program::{Goto, Movement, ProgramBuilder, Response};
+
+/// Machines in busy beaver notation start in `A`, the state numbered 0, on a tape of 0s.
+pub const INITIAL: usize = 0;
+
+/// The symbol a blank tape is filled with.
+pub const BLANK: usize = 0;
+
+/// The triple standing for a transition the machine doesn't define. Reaching one halts, as
+/// it does in the usual notation, leaving the tape and head as they are.
+const UNDEFINED: &str = "---";
+
+/// The letter written for halting; any letter past the last state halts too.
+const HALT: char = 'Z';
+
+/// Notation that isn't a busy beaver machine, or a machine the notation can't express.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BusyBeaverError(String);
+
+/// Parses busy beaver notation, such as `1RB1LB_1LA1RZ`: one group per state, from `A`,
+/// separated by `_`, each listing a write, move and next state per symbol, from `0`.
+/// A next state past the last one halts and accepts, and so does an undefined `---` transition.
+pub fn parse(code: &str) -> Result<ProgramBuilder<usize, usize>, BusyBeaverError> {
+    let groups = code.trim().split('_').collect::<Vec<_>>();
+    if groups.len() > 26 {
+        return Err(BusyBeaverError(format!(
+            "Only 26 states can be named, but there are {}.",
+            groups.len()
+        )));
+    }
+    let symbol_count = groups[0].len() / 3;
+    let mut builder = ProgramBuilder::new();
+    for (state, group) in groups.iter().enumerate() {
+        let name = state_name(state);
+        if group.len() != 3 * symbol_count || symbol_count == 0 || !group.is_ascii() {
+            return Err(BusyBeaverError(format!(
+                "State {} should have {} transitions of three characters each, not {:?}.",
+                name,
+                symbol_count.max(1),
+                group
+            )));
+        }
+        for read in 0..symbol_count {
+            let triple = &group[3 * read..3 * read + 3];
+            if triple == UNDEFINED {
+                builder.add_transition((state, read), undefined(read));
+                continue;
+            }
+            let invalid = || {
+                BusyBeaverError(format!(
+                    "State {} reading {} has transition {:?}, expected something like 1RB.",
+                    name, read, triple
+                ))
+            };
+            let mut chars = triple.chars();
+            let write = chars
+                .next()
+                .and_then(|c| c.to_digit(10))
+                .map(|d| d as usize)
+                .filter(|&d| d < symbol_count)
+                .ok_or_else(invalid)?;
+            let mv = match chars.next() {
+                Some('L') => Movement::Left,
+                Some('R') => Movement::Right,
+                _ => return Err(invalid()),
+            };
+            let goto = match chars.next() {
+                Some(c @ 'A'..='Z') => {
+                    let next = (c as u8 - b'A') as usize;
+                    if next < groups.len() {
+                        Goto::Run(next)
+                    } else {
+                        Goto::Halt(true)
+                    }
+                }
+                _ => return Err(invalid()),
+            };
+            builder.add_transition((state, read), (goto, write, Some(mv)));
+        }
+    }
+    Ok(builder)
+}
+
+/// Writes a machine in busy beaver notation, with `Z` for halting and `---` for a missing
+/// transition or one that halts without writing or moving. States and symbols are numbered from 0 up to the largest one it uses.
+pub fn emit(builder: &ProgramBuilder<usize, usize>) -> Result<String, BusyBeaverError> {
+    if builder.has_wildcards() {
+        return Err(BusyBeaverError(
+            "Wildcard transitions can't be written in busy beaver notation.".into(),
+        ));
+    }
+    let mut table = HashMap::new();
+    let mut state_count = INITIAL + 1;
+    let mut symbol_count = 2;
+    for (&state, &read, response) in builder.transitions() {
+        table.insert((state, read), response);
+        state_count = state_count.max(state + 1);
+        symbol_count = symbol_count.max(read + 1).max(response.write + 1);
+        if let Goto::Run(next) | Goto::Emit(next) = response.goto {
+            state_count = state_count.max(next + 1);
+        }
+    }
+    if state_count > 26 || symbol_count > 10 {
+        return Err(BusyBeaverError(format!(
+            "Busy beaver notation can't name {} states and {} symbols.",
+            state_count, symbol_count
+        )));
+    }
+
+    let mut groups = Vec::new();
+    for state in 0..state_count {
+        let mut group = String::new();
+        for read in 0..symbol_count {
+            match table.get(&(state, read)) {
+                Some(&response) if *response != undefined(read) => {
+                    group.push_str(&triple(state, read, response)?)
+                }
+                _ => group.push_str(UNDEFINED),
+            }
+        }
+        groups.push(group);
+    }
+    Ok(groups.join("_"))
+}
+
+fn triple(
+    state: usize,
+    read: usize,
+    response: &Response<usize, usize>,
+) -> Result<String, BusyBeaverError> {
+    let unsupported = |what: &str| {
+        BusyBeaverError(format!(
+            "State {} reading {} {}, which busy beaver notation can't express.",
+            state_name(state),
+            read,
+            what
+        ))
+    };
+    if response.output.is_some() {
+        return Err(unsupported("writes output"));
+    }
+    let mv = match response.mv {
+        Some(Movement::Left) => 'L',
+        Some(Movement::Right) => 'R',
+        None => return Err(unsupported("stays put")),
+    };
+    let next = match response.goto {
+        Goto::Run(next) => state_name(next),
+        Goto::Halt(true) => HALT,
+        Goto::Halt(false) => return Err(unsupported("rejects")),
+        Goto::Emit(_) => return Err(unsupported("prints a word")),
+    };
+    Ok(format!("{}{}{}", response.write, mv, next))
+}
+
+/// What an undefined transition does: halt and accept, changing nothing.
+pub fn undefined(read: usize) -> Response<usize, usize> {
+    Response::new(Goto::Halt(true), read, None)
+}
+
+fn state_name(state: usize) -> char {
+    (b'A' + state as u8) as char
+}
+
+impl fmt::Display for BusyBeaverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for BusyBeaverError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tape::{trim_blanks, Unbounded},
+        turing_machine::TuringMachine,
+    };
+
+    const BB2: &str = "1RB1LB_1LA1RZ";
+
+    #[test]
+    fn runs_the_two_state_champion() {
+        let prog = parse(BB2).expect("Valid machine.").build(INITIAL);
+        let mut machine = TuringMachine::new(prog, Unbounded::with_blank(BLANK));
+        let mut steps = 0;
+        while machine.step().is_none() {
+            steps += 1;
+        }
+        assert_eq!(steps, 6);
+        let tape = trim_blanks(machine.get_tape(), BLANK).collect::<Vec<_>>();
+        assert_eq!(tape, vec![1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn round_trip() {
+        for code in &[BB2, "1RB---_0LA1RA", "1RB2LA1RZ_2LA2RB1LB"] {
+            let builder = parse(code).expect("Valid machine.");
+            assert_eq!(emit(&builder).as_deref(), Ok(*code));
+        }
+    }
+
+    #[test]
+    fn undefined_transitions_halt() {
+        let prog = parse("1RB---_0LA1RA")
+            .expect("Valid machine.")
+            .build(INITIAL);
+        let mut machine = TuringMachine::new(prog, Unbounded::with_blank(BLANK));
+        assert!(machine.run());
+        let tape = trim_blanks(machine.get_tape(), BLANK).collect::<Vec<_>>();
+        assert_eq!(tape, vec![1]);
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(parse("1RB1LB_1LA").is_err());
+        assert!(parse("1XB1LB_1LA1RZ").is_err());
+        assert!(parse("2RB1LB_1LA1RZ").is_err());
+        assert!(parse("").is_err());
+    }
+}
@@ -0,0 +1,164 @@
+use std::{error::Error, fmt, fs, path::Path, str::FromStr};
+
+use crate::{
+    jflap, morphett, post_turing,
+    program_ron::{ProgramFile, Syntax},
+    spreadsheet, turing1936, turingmachine_io,
+};
+
+/// The file formats programs can be read from and written to, told apart by extension
+/// or named with `--format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Ron,
+    /// The RON schema in JSON, `.json`.
+    Json,
+    /// The RON schema in YAML, `.yaml` or `.yml`. A turingmachine.io program is read too.
+    Yaml,
+    /// The RON schema in TOML, `.toml`.
+    Toml,
+    /// JFLAP's XML, `.jff`.
+    Jflap,
+    /// turingmachine.io's YAML, whatever the extension.
+    TuringMachineIo,
+    /// Quintuples one per line, as used by morphett.info, `.tm`.
+    Morphett,
+    /// A spreadsheet's transition table, `.csv`.
+    Csv,
+    /// Turing's own tables of m-configurations, `.turing`.
+    Turing1936,
+    /// Numbered Post–Turing instructions, `.pt`, over `0` for blanks and `1` for marks.
+    PostTuring,
+}
+
+/// The names `--format` accepts, in the order of `Format`'s variants.
+const NAMES: [(&str, Format); 10] = [
+    ("ron", Format::Ron),
+    ("json", Format::Json),
+    ("yaml", Format::Yaml),
+    ("toml", Format::Toml),
+    ("jflap", Format::Jflap),
+    ("turingmachine.io", Format::TuringMachineIo),
+    ("morphett", Format::Morphett),
+    ("csv", Format::Csv),
+    ("turing1936", Format::Turing1936),
+    ("post-turing", Format::PostTuring),
+];
+
+impl Format {
+    /// Anything without a known extension is taken to be RON.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Format::Json,
+            Some("yaml") | Some("yml") => Format::Yaml,
+            Some("toml") => Format::Toml,
+            Some("jff") => Format::Jflap,
+            Some("tm") => Format::Morphett,
+            Some("csv") => Format::Csv,
+            Some("turing") => Format::Turing1936,
+            Some("pt") => Format::PostTuring,
+            _ => Format::Ron,
+        }
+    }
+
+    /// The syntax `code` is written in, if it follows the RON schema. YAML follows it unless
+    /// it's turingmachine.io's, with a `table` and `start state`.
+    pub fn syntax(self, code: &str) -> Option<Syntax> {
+        match self {
+            Format::Ron => Some(Syntax::Ron),
+            Format::Json => Some(Syntax::Json),
+            Format::Yaml if !turingmachine_io::is_turingmachine_io(code) => Some(Syntax::Yaml),
+            Format::Toml => Some(Syntax::Toml),
+            _ => None,
+        }
+    }
+
+    /// YAML is read as turingmachine.io's if it has a `table` and `start state`,
+    /// and with the RON schema otherwise.
+    pub fn read(self, path: &Path) -> Result<ProgramFile, Box<dyn Error>> {
+        match self {
+            Format::Ron => Ok(ProgramFile::load_as(path, Syntax::Ron)?),
+            Format::Json => Ok(ProgramFile::load_as(path, Syntax::Json)?),
+            Format::Toml => Ok(ProgramFile::load_as(path, Syntax::Toml)?),
+            Format::Yaml => {
+                let yaml = fs::read_to_string(path)?;
+                match self.syntax(&yaml) {
+                    Some(syntax) => Ok(ProgramFile::load_as(path, syntax)?),
+                    None => Ok(turingmachine_io::read(&yaml)?),
+                }
+            }
+            Format::Jflap => Ok(jflap::read(&fs::read_to_string(path)?)?),
+            Format::TuringMachineIo => Ok(turingmachine_io::read(&fs::read_to_string(path)?)?),
+            Format::Morphett => Ok(morphett::read(&fs::read_to_string(path)?)?),
+            Format::Csv => Ok(spreadsheet::read(&fs::read_to_string(path)?)?),
+            Format::Turing1936 => Ok(turing1936::read(&fs::read_to_string(path)?)?),
+            Format::PostTuring => Ok(post_turing::read(&fs::read_to_string(path)?)?),
+        }
+    }
+
+    /// YAML is written in the RON schema; turingmachine.io's has to be asked for by name.
+    /// JSON, TOML and Turing's tables can only be read. `halt_anyway` lets a Post–Turing
+    /// program halt where the machine rejects or has no transition.
+    pub fn write(self, prog: &ProgramFile, halt_anyway: bool) -> Result<String, Box<dyn Error>> {
+        match self {
+            Format::Ron => Ok(prog.to_ron()),
+            Format::Yaml => Ok(prog.to_yaml()),
+            Format::Json | Format::Toml | Format::Turing1936 => {
+                Err(format!("Programs can be read from {}, but not written to it.", self).into())
+            }
+            Format::Jflap => Ok(jflap::write(prog)?),
+            Format::TuringMachineIo => Ok(turingmachine_io::write(prog)?),
+            Format::Morphett => Ok(morphett::write(prog)?),
+            Format::Csv => Ok(spreadsheet::write(prog)?),
+            Format::PostTuring => Ok(post_turing::write(prog, halt_anyway)?),
+        }
+    }
+}
+
+/// Reads a Turing machine program in the given format, or whichever its extension says.
+pub fn load(path: &Path, format: Option<Format>) -> Result<ProgramFile, Box<dyn Error>> {
+    format.unwrap_or_else(|| Format::from_path(path)).read(path)
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (name, _) = NAMES
+            .iter()
+            .find(|(_, format)| format == self)
+            .expect("Every format has a name.");
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        NAMES
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(s))
+            .map(|&(_, format)| format)
+            .ok_or_else(|| {
+                let names = NAMES.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+                format!(
+                    "Unknown format {:?}, expected one of {}.",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        for &(name, format) in &NAMES {
+            assert_eq!(name.parse(), Ok(format));
+            assert_eq!(format.to_string(), name);
+        }
+        assert!("xml".parse::<Format>().is_err());
+    }
+}
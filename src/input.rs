@@ -0,0 +1,236 @@
+use smol_str::SmolStr;
+use unicode_segmentation::UnicodeSegmentation;
+
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    io,
+    io::{BufRead, ErrorKind},
+    rc::Rc,
+    str,
+};
+
+/// Lazily splits the contents of a reader into extended grapheme clusters.
+/// Input is segmented a buffer at a time. The last cluster of each buffer is held back until
+/// more input arrives, since the next byte might extend it, so memory stays bounded by the
+/// reader's buffer no matter how long the lines are.
+#[derive(Debug)]
+pub struct Graphemes<R> {
+    rdr: R,
+    /// Bytes read but not yet split off: a trailing cluster, and maybe an incomplete character.
+    held: Vec<u8>,
+    pending: VecDeque<SmolStr>,
+    /// Reported once the clusters before it have been taken.
+    error: Option<io::Error>,
+    done: bool,
+}
+
+impl<R> Graphemes<R>
+where
+    R: BufRead,
+{
+    pub fn new(rdr: R) -> Self {
+        Self {
+            rdr,
+            held: Vec::new(),
+            pending: VecDeque::new(),
+            error: None,
+            done: false,
+        }
+    }
+
+    /// Reads one more buffer, splitting off every cluster that's known to be complete.
+    fn fill(&mut self) -> io::Result<()> {
+        let chunk = self.rdr.fill_buf()?;
+        let at_end = chunk.is_empty();
+        self.held.extend_from_slice(chunk);
+        let len = chunk.len();
+        self.rdr.consume(len);
+
+        let (valid, complete) = match str::from_utf8(&self.held) {
+            Ok(text) => (text, at_end),
+            Err(e) => {
+                let valid = &self.held[..e.valid_up_to()];
+                let valid = str::from_utf8(valid).expect("Checked to be valid.");
+                if e.error_len().is_none() && !at_end {
+                    // The buffer ended partway through a character.
+                    (valid, false)
+                } else {
+                    self.error = Some(io::Error::new(ErrorKind::InvalidData, e));
+                    (valid, true)
+                }
+            }
+        };
+        let mut clusters = valid.graphemes(true).collect::<Vec<_>>();
+        let kept = if complete {
+            self.done = true;
+            0
+        } else {
+            clusters.pop().map_or(0, str::len)
+        };
+        let split = valid.len() - kept;
+        self.pending.extend(clusters.into_iter().map(SmolStr::from));
+        self.held.drain(..split);
+        Ok(())
+    }
+}
+
+impl<R> Iterator for Graphemes<R>
+where
+    R: BufRead,
+{
+    type Item = io::Result<SmolStr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() && !self.done {
+            if let Err(e) = self.fill() {
+                return Some(Err(e));
+            }
+        }
+        match self.pending.pop_front() {
+            Some(cluster) => Some(Ok(cluster)),
+            None => self.error.take().map(Err),
+        }
+    }
+}
+
+/// Yields the items of a fallible iterator until its first error, which is set aside in the
+/// `Failure` returned alongside it. This lets a tape pull input lazily while the error still
+/// reaches whoever is running the machine.
+pub fn until_error<I, T, E>(iter: I) -> (UntilError<I::IntoIter, E>, Failure<E>)
+where
+    I: IntoIterator<Item = Result<T, E>>,
+{
+    let failure = Rc::new(RefCell::new(None));
+    let iter = UntilError {
+        iter: iter.into_iter(),
+        failure: Rc::clone(&failure),
+        failed: false,
+    };
+    (iter, Failure(failure))
+}
+
+#[derive(Debug)]
+pub struct UntilError<I, E> {
+    iter: I,
+    failure: Rc<RefCell<Option<E>>>,
+    failed: bool,
+}
+
+impl<I, T, E> Iterator for UntilError<I, E>
+where
+    I: Iterator<Item = Result<T, E>>,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.failed {
+            return None;
+        }
+        match self.iter.next()? {
+            Ok(item) => Some(item),
+            Err(e) => {
+                self.failed = true;
+                *self.failure.borrow_mut() = Some(e);
+                None
+            }
+        }
+    }
+}
+
+/// The error that stopped an `until_error` iterator, if it has stopped on one.
+#[derive(Debug)]
+pub struct Failure<E>(Rc<RefCell<Option<E>>>);
+
+impl<E> Failure<E> {
+    /// Takes the error, if there is one yet.
+    pub fn check(&self) -> Result<(), E> {
+        match self.0.borrow_mut().take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufReader, Read};
+
+    #[test]
+    fn splits_graphemes_across_lines() {
+        let input = "ae\u{301}\r\nb\n🇬🇧";
+        let graphemes = Graphemes::new(input.as_bytes())
+            .collect::<io::Result<Vec<_>>>()
+            .expect("Reading from a byte slice cannot fail.");
+        assert_eq!(graphemes, vec!["a", "e\u{301}", "\r\n", "b", "\n", "🇬🇧"]);
+    }
+
+    #[test]
+    fn splits_graphemes_across_buffers() {
+        // A one-byte buffer splits every multi-byte character and cluster.
+        let input = "ae\u{301}🇬🇧\r\nb";
+        let graphemes = Graphemes::new(BufReader::with_capacity(1, input.as_bytes()))
+            .collect::<io::Result<Vec<_>>>()
+            .expect("Reading from a byte slice cannot fail.");
+        assert_eq!(graphemes, vec!["a", "e\u{301}", "🇬🇧", "\r\n", "b"]);
+    }
+
+    #[test]
+    fn does_not_wait_for_a_line() {
+        // Like a pipe that sends some input and then waits forever.
+        struct Stalled(bool);
+        impl Read for Stalled {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                assert!(!self.0, "Read past the input that was available.");
+                self.0 = true;
+                buf[..3].copy_from_slice(b"abc");
+                Ok(3)
+            }
+        }
+        let mut graphemes = Graphemes::new(BufReader::new(Stalled(false)));
+        assert_eq!(
+            graphemes.next().expect("Some input.").ok(),
+            Some("a".into())
+        );
+        assert_eq!(
+            graphemes.next().expect("Some input.").ok(),
+            Some("b".into())
+        );
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let mut graphemes = Graphemes::new(&b"\xff\xfe"[..]);
+        assert!(graphemes.next().expect("Should report the error.").is_err());
+        assert!(graphemes.next().is_none());
+        let mut graphemes = Graphemes::new(&b"ab\xffc"[..]);
+        assert_eq!(
+            graphemes.next().expect("Some input.").ok(),
+            Some("a".into())
+        );
+        assert_eq!(
+            graphemes.next().expect("Some input.").ok(),
+            Some("b".into())
+        );
+        assert!(graphemes.next().expect("Should report the error.").is_err());
+        assert!(graphemes.next().is_none());
+        let mut graphemes = Graphemes::new(&b"a\xe2\x82"[..]);
+        assert_eq!(
+            graphemes.next().expect("Some input.").ok(),
+            Some("a".into())
+        );
+        assert!(graphemes.next().expect("Truncated character.").is_err());
+    }
+
+    #[test]
+    fn stops_at_the_first_error() {
+        let items = vec![Ok(1), Ok(2), Err("bad"), Ok(3)];
+        let (mut iter, failure) = until_error(items);
+        assert_eq!(iter.next(), Some(1));
+        assert_eq!(failure.check(), Ok(()));
+        assert_eq!(iter.by_ref().collect::<Vec<_>>(), vec![2]);
+        assert_eq!(iter.next(), None);
+        assert_eq!(failure.check(), Err("bad"));
+    }
+}
@@ -0,0 +1,294 @@
+use roxmltree::{Document, Node};
+
+use std::{collections::HashMap, error::Error, fmt};
+
+use crate::{
+    program::Movement,
+    program_ron::{ProgramFile, Rule},
+};
+
+/// A `.jff` file that isn't a single-tape Turing machine we can read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JflapError(String);
+
+/// Reads a JFLAP Turing machine. Its final states accept, other states without transitions
+/// reject, and an empty symbol is the blank.
+pub fn read(xml: &str) -> Result<ProgramFile, JflapError> {
+    let doc = Document::parse(xml)?;
+    let root = doc.root_element();
+    match child_text(root, "type") {
+        Some("turing") => {}
+        Some(other) => return Err(JflapError(format!("This is a JFLAP {} file.", other))),
+        None => return Err(JflapError("This is not a JFLAP file.".into())),
+    }
+    if let Some(tapes) = child_text(root, "tapes") {
+        if tapes.trim() != "1" {
+            return Err(JflapError(format!(
+                "The machine uses {} tapes, but only single-tape machines are supported.",
+                tapes.trim()
+            )));
+        }
+    }
+    if root.descendants().any(|node| node.has_tag_name("block")) {
+        return Err(JflapError("Building blocks are not supported.".into()));
+    }
+
+    let mut names = HashMap::new();
+    let mut initial = None;
+    let mut finals = Vec::new();
+    for state in root.descendants().filter(|node| node.has_tag_name("state")) {
+        let id = state
+            .attribute("id")
+            .ok_or_else(|| JflapError("A state has no id.".into()))?;
+        let name = state
+            .attribute("name")
+            .map_or_else(|| format!("q{}", id), String::from);
+        if names.values().any(|other| *other == name) {
+            return Err(JflapError(format!("Two states are named {:?}.", name)));
+        }
+        if has_child(state, "initial") && initial.replace(name.clone()).is_some() {
+            return Err(JflapError(
+                "The machine has more than one initial state.".into(),
+            ));
+        }
+        if has_child(state, "final") {
+            finals.push(name.clone());
+        }
+        names.insert(id.to_owned(), name);
+    }
+    let initial = initial.ok_or_else(|| JflapError("The machine has no initial state.".into()))?;
+
+    let mut prog = ProgramFile::new(initial, "");
+    let mut rules = Vec::new();
+    for transition in root
+        .descendants()
+        .filter(|node| node.has_tag_name("transition"))
+    {
+        let state = |tag: &str| {
+            let id = child_text(transition, tag).unwrap_or_default().trim();
+            names.get(id).cloned().ok_or_else(|| {
+                JflapError(format!("A transition refers to a missing state {:?}.", id))
+            })
+        };
+        let mv = match child_text(transition, "move").map(str::trim) {
+            Some("L") => Some(Movement::Left),
+            Some("R") => Some(Movement::Right),
+            Some("S") => None,
+            other => {
+                return Err(JflapError(format!(
+                    "Unknown move {:?}, expected L, R or S.",
+                    other.unwrap_or_default()
+                )))
+            }
+        };
+        rules.push(Rule {
+            state: state("from")?,
+            read: child_text(transition, "read")
+                .unwrap_or_default()
+                .to_owned(),
+            goto: state("to")?,
+            write: child_text(transition, "write")
+                .unwrap_or_default()
+                .to_owned(),
+            mv,
+        });
+    }
+
+    let mut dead_ends = names
+        .values()
+        .filter(|state| !finals.contains(state) && !rules.iter().any(|rule| rule.state == **state))
+        .cloned()
+        .collect::<Vec<_>>();
+    dead_ends.sort();
+    prog.set_halting(finals, dead_ends);
+    for rule in rules {
+        prog.add_rule(rule)?;
+    }
+    Ok(prog)
+}
+
+/// Writes a program as a JFLAP Turing machine. Accepting states become final states,
+/// and rejecting ones plain states without transitions.
+pub fn write(prog: &ProgramFile) -> Result<String, JflapError> {
+    let rules = prog.rules()?;
+    let blank = prog.blank();
+
+    let mut states: Vec<&str> = vec![prog.initial()];
+    for rule in &rules {
+        for state in &[&rule.state, &rule.goto] {
+            if !states.contains(&state.as_str()) {
+                states.push(state);
+            }
+        }
+    }
+    let id = |state: &str| states.iter().position(|s| *s == state).unwrap_or_default();
+    let symbol = |symbol: &str| {
+        if symbol == blank {
+            String::new()
+        } else {
+            escape(symbol)
+        }
+    };
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n\
+         <structure>\n\
+         \t<type>turing</type>\n\
+         \t<automaton>\n",
+    );
+    for (i, state) in states.iter().enumerate() {
+        xml.push_str(&format!(
+            "\t\t<state id=\"{}\" name=\"{}\">\n\t\t\t<x>{}.0</x>\n\t\t\t<y>{}.0</y>\n",
+            i,
+            escape(state),
+            100 + 150 * (i % 5),
+            100 + 150 * (i / 5)
+        ));
+        if i == 0 {
+            xml.push_str("\t\t\t<initial/>\n");
+        }
+        if prog.accept_states().iter().any(|s| s == state) {
+            xml.push_str("\t\t\t<final/>\n");
+        }
+        xml.push_str("\t\t</state>\n");
+    }
+    for rule in &rules {
+        let mv = match rule.mv {
+            Some(Movement::Left) => "L",
+            Some(Movement::Right) => "R",
+            None => "S",
+        };
+        xml.push_str(&format!(
+            "\t\t<transition>\n\t\t\t<from>{}</from>\n\t\t\t<to>{}</to>\n\
+             \t\t\t<read>{}</read>\n\t\t\t<write>{}</write>\n\t\t\t<move>{}</move>\n\
+             \t\t</transition>\n",
+            id(&rule.state),
+            id(&rule.goto),
+            symbol(&rule.read),
+            symbol(&rule.write),
+            mv
+        ));
+    }
+    xml.push_str("\t</automaton>\n</structure>\n");
+    Ok(xml)
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, tag: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(tag))
+}
+
+fn has_child(node: Node<'_, '_>, tag: &str) -> bool {
+    child(node, tag).is_some()
+}
+
+/// The text of a child element, `Some("")` if it's empty.
+fn child_text<'a>(node: Node<'a, '_>, tag: &str) -> Option<&'a str> {
+    child(node, tag).map(|child| child.text().unwrap_or_default())
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl fmt::Display for JflapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for JflapError {}
+
+impl From<roxmltree::Error> for JflapError {
+    fn from(e: roxmltree::Error) -> Self {
+        JflapError(format!("Malformed XML: {}", e))
+    }
+}
+
+impl From<ron::de::Error> for JflapError {
+    fn from(e: ron::de::Error) -> Self {
+        JflapError(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FLIP: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?><!--Created with JFLAP 7.1.--><structure>
+	<type>turing</type>
+	<automaton>
+		<!--The list of states.-->
+		<state id="0" name="q0">
+			<x>60.0</x>
+			<y>120.0</y>
+			<initial/>
+		</state>
+		<state id="1" name="q1">
+			<x>200.0</x>
+			<y>120.0</y>
+			<final/>
+		</state>
+		<!--The list of transitions.-->
+		<transition>
+			<from>0</from>
+			<to>0</to>
+			<read>a</read>
+			<write>b</write>
+			<move>R</move>
+		</transition>
+		<transition>
+			<from>0</from>
+			<to>1</to>
+			<read/>
+			<write/>
+			<move>S</move>
+		</transition>
+	</automaton>
+</structure>"#;
+
+    #[test]
+    fn import() {
+        let prog = read(FLIP).expect("Valid JFLAP machine.");
+        assert_eq!(prog.initial(), "q0");
+        assert_eq!(prog.accept_states(), &["q1".to_string()]);
+        assert_eq!(
+            prog.rules().expect("Single track."),
+            vec![
+                Rule {
+                    state: "q0".into(),
+                    read: "".into(),
+                    goto: "q1".into(),
+                    write: "".into(),
+                    mv: None,
+                },
+                Rule {
+                    state: "q0".into(),
+                    read: "a".into(),
+                    goto: "q0".into(),
+                    write: "b".into(),
+                    mv: Some(Movement::Right),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let prog = read(FLIP).expect("Valid JFLAP machine.");
+        let xml = write(&prog).expect("Exportable.");
+        let again = read(&xml).expect("Exports valid JFLAP.");
+        assert_eq!(again.rules(), prog.rules());
+        assert_eq!(again.accept_states(), prog.accept_states());
+    }
+
+    #[test]
+    fn not_a_turing_machine() {
+        let xml = "<structure><type>fa</type><automaton/></structure>";
+        assert!(read(xml).is_err());
+        let xml = FLIP.replace("<type>turing</type>", "<type>turing</type><tapes>2</tapes>");
+        assert!(read(&xml).is_err());
+    }
+}
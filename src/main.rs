@@ -1,75 +1,567 @@
-#[warn(rust_2018_idioms)]
+#![warn(rust_2018_idioms)]
 
+pub mod automaton;
+pub mod bbchallenge;
+pub mod busy_beaver;
+pub mod diagnostic;
+pub mod format;
+pub mod input;
+pub mod jflap;
+pub mod morphett;
+pub mod multitrack;
+pub mod numeric;
+pub mod post_turing;
 pub mod program;
 pub mod program_ron;
+pub mod reversible;
+pub mod spreadsheet;
 pub mod tape;
+pub mod turing1936;
 pub mod turing_machine;
+pub mod turingmachine_io;
 
-use std::{fs::File, io, io::Read, path::PathBuf};
+use std::{
+    error::Error,
+    fs,
+    fs::File,
+    io,
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process,
+};
 
 use smol_str::SmolStr;
 use structopt::StructOpt;
-use unicode_segmentation::UnicodeSegmentation;
 
+use diagnostic::Diagnostic;
+use format::Format;
+use input::{until_error, Failure, Graphemes};
+use multitrack::Tracks;
+use numeric::Encoding;
 use program::{Goto, Movement, Response, TransitionFn};
-use tape::Unbounded;
+use program_ron::Kind;
+use tape::{trim_blanks, Streamed, Tape, Unbounded};
 use turing_machine::TuringMachine;
 
 #[derive(Debug, StructOpt)]
-struct Opt {
+#[structopt(name = "tm")]
+enum Opt {
+    /// Runs a program on its input and prints the final tape.
+    Run(RunOpt),
+    /// Computes a numeric function of the given arguments.
+    Compute(ComputeOpt),
+    /// Runs an enumerator on a blank tape, printing each word it enumerates on its own line.
+    Enumerate(EnumerateOpt),
+    /// Checks that a program is reversible, then runs it backwards from the tape it halted with.
+    Reverse(ReverseOpt),
+    /// Converts a program between formats, chosen by the files' extensions unless named.
+    Convert(ConvertOpt),
+    /// Runs machines from a bbchallenge database with a step limit, writing a CSV of outcomes.
+    Batch(BatchOpt),
+    /// Turns a machine over two symbols, from a file or in busy beaver notation, into a
+    /// Post–Turing program.
+    Decompile(DecompileOpt),
+}
+
+#[derive(Debug, StructOpt)]
+struct RunOpt {
+    /// The program. A Post–Turing program runs on 1s for marks and 0s for blanks.
+    #[structopt(parse(from_os_str), required_unless = "bb")]
+    file: Option<PathBuf>,
+
+    /// Run a machine given in busy beaver notation, such as 1RB1LB_1LA1RZ, on a blank tape.
+    #[structopt(long, conflicts_with = "file")]
+    bb: Option<String>,
+
+    #[structopt(short, long)]
+    debug: bool,
+
+    /// Read the input tape from this file instead of stdin.
+    #[structopt(short, long, parse(from_os_str))]
+    input: Option<PathBuf>,
+
+    /// Run the program even if validating it raised warnings.
+    #[structopt(long)]
+    allow_warnings: bool,
+
+    #[structopt(flatten)]
+    format: FormatOpt,
+}
+
+/// How to tell which format a program file is in.
+#[derive(Debug, StructOpt)]
+struct FormatOpt {
+    /// The program's format: ron, json, yaml, toml, jflap, turingmachine.io, morphett, csv,
+    /// turing1936 or post-turing.
+    /// Taken from the file's extension if not given.
+    #[structopt(long)]
+    format: Option<Format>,
+}
+
+#[derive(Debug, StructOpt)]
+struct ConvertOpt {
+    #[structopt(parse(from_os_str))]
+    from: PathBuf,
+
+    /// Where to write the converted program.
+    #[structopt(parse(from_os_str))]
+    to: PathBuf,
+
+    /// The format to write, such as turingmachine.io. Taken from the extension of the file
+    /// written to if not given.
+    #[structopt(long)]
+    to_format: Option<Format>,
+
+    /// Let a Post–Turing program halt, and so accept, where the machine rejects or has no
+    /// transition.
+    #[structopt(long)]
+    halt_anyway: bool,
+
+    #[structopt(flatten)]
+    format: FormatOpt,
+}
+
+#[derive(Debug, StructOpt)]
+struct DecompileOpt {
+    /// The program, which may only use its blank and one other symbol.
+    #[structopt(parse(from_os_str), required_unless = "bb")]
+    file: Option<PathBuf>,
+
+    /// Decompile a machine given in busy beaver notation, such as 1RB1LB_1LA1RZ.
+    #[structopt(long, conflicts_with = "file")]
+    bb: Option<String>,
+
+    /// Where to write the numbered instructions, usually a `.pt` file.
+    #[structopt(short, long, parse(from_os_str))]
+    output: PathBuf,
+
+    /// Let the program halt, and so accept, where the machine rejects or has no transition.
+    #[structopt(long)]
+    halt_anyway: bool,
+
+    #[structopt(flatten)]
+    format: FormatOpt,
+}
+
+#[derive(Debug, StructOpt)]
+struct BatchOpt {
+    #[structopt(parse(from_os_str))]
+    database: PathBuf,
+
+    /// The index of the first machine to run, counting from 0.
+    #[structopt(long, default_value = "0")]
+    from: u64,
+
+    /// The index just past the last machine to run; the end of the database if not given.
+    #[structopt(long)]
+    to: Option<u64>,
+
+    /// Give up on a machine after this many steps.
+    #[structopt(short, long, default_value = "47176870")]
+    steps: u64,
+
+    /// Write the CSV to this file instead of stdout.
+    #[structopt(short, long, parse(from_os_str))]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, StructOpt)]
+struct ComputeOpt {
+    #[structopt(parse(from_os_str))]
+    file: PathBuf,
+
+    /// How the arguments and result are written on the tape: unary, binary or decimal.
+    #[structopt(short, long, default_value = "unary")]
+    encoding: Encoding,
+
+    args: Vec<u64>,
+
+    #[structopt(flatten)]
+    format: FormatOpt,
+}
+
+#[derive(Debug, StructOpt)]
+struct EnumerateOpt {
     #[structopt(parse(from_os_str))]
     file: PathBuf,
 
+    /// Stop after printing this many words.
     #[structopt(short, long)]
-    debug: bool,
+    words: Option<u64>,
+
+    /// Stop after running this many steps.
+    #[structopt(short, long)]
+    steps: Option<u64>,
+
+    #[structopt(flatten)]
+    format: FormatOpt,
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let opt = Opt::from_args();
-    run(opt)
+#[derive(Debug, StructOpt)]
+struct ReverseOpt {
+    #[structopt(parse(from_os_str))]
+    file: PathBuf,
+
+    /// Only check that the program is reversible.
+    #[structopt(short, long)]
+    check: bool,
+
+    /// Where the R/W head stopped, counting from the first cell of the final tape as given,
+    /// blanks included.
+    #[structopt(long, default_value = "0")]
+    head: usize,
+
+    /// The machine halted by rejecting rather than accepting.
+    #[structopt(short, long)]
+    reject: bool,
+
+    /// Give up after undoing this many steps.
+    #[structopt(short, long, default_value = "10000000")]
+    steps: u64,
+
+    /// Read the final tape from this file instead of stdin. It's read as given, starting from
+    /// the cell the input started on, so blanks the machine left before the first symbol have
+    /// to be kept even though `run` trims them.
+    #[structopt(short, long, parse(from_os_str))]
+    input: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    format: FormatOpt,
 }
 
-fn run(opt: Opt) -> Result<(), Box<dyn std::error::Error>> {
-    let (init, tr_func) = program_ron::read_program(File::open(opt.file)?)?;
+fn main() {
+    let result = match Opt::from_args() {
+        Opt::Run(opt) => run(opt),
+        Opt::Compute(opt) => compute(opt),
+        Opt::Enumerate(opt) => enumerate(opt),
+        Opt::Reverse(opt) => reverse(opt),
+        Opt::Convert(opt) => convert(opt),
+        Opt::Batch(opt) => batch(opt),
+        Opt::Decompile(opt) => decompile(opt),
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
 
-    let mut input_buf = Vec::new();
-    io::stdin().read_to_end(&mut input_buf)?;
-    let input = String::from_utf8(input_buf)?;
-    let graphemes = UnicodeSegmentation::graphemes(input.as_str(), true);
-    let tape: Unbounded<SmolStr> = graphemes.map(|g| SmolStr::from(g)).collect();
+/// How many configurations a pushdown automaton may explore before we give up on it.
+const PDA_LIMIT: usize = 1_000_000;
 
-    let mut machine = TuringMachine::new(init, tr_func, tape);
+fn run(opt: RunOpt) -> Result<(), Box<dyn Error>> {
+    let file = match (&opt.file, &opt.bb) {
+        (_, Some(code)) => return run_busy_beaver(code, opt.debug),
+        (Some(file), None) => file,
+        (None, None) => return Err("Give a program file or --bb.".into()),
+    };
+    let graphemes = read_input(opt.input.clone())?;
+    // Only programs in the RON schema describe anything other than a Turing machine.
+    let format = opt.format.format.unwrap_or_else(|| Format::from_path(file));
+    let code = fs::read_to_string(file)?;
+    let syntax = match format.syntax(&code) {
+        Some(syntax) => syntax,
+        None => return run_turing(graphemes, file, &opt),
+    };
+    let kind = program_ron::read_kind(&code, syntax)?;
+    if kind == Kind::Turing {
+        return run_turing(graphemes, file, &opt);
+    }
 
-    let accept = if opt.debug {
+    if opt.debug {
+        return Err("--debug only steps through Turing machines.".into());
+    }
+    let (graphemes, failure) = until_error(graphemes);
+    let accept = match kind {
+        Kind::Turing => unreachable!("Turing machines were run above."),
+        Kind::Dfa => program_ron::read_dfa(&code, syntax)?.accepts(graphemes),
+        Kind::Nfa => program_ron::read_nfa(&code, syntax)?.accepts(graphemes),
+        Kind::Pda => program_ron::read_pda(&code, syntax)?
+            .accepts(graphemes, PDA_LIMIT)
+            .ok_or("The pushdown automaton explored too many configurations to decide.")?,
+    };
+    failure.check()?;
+    println!("{}", accept);
+    Ok(())
+}
+
+fn run_turing(
+    graphemes: impl Iterator<Item = io::Result<SmolStr>> + 'static,
+    file: &Path,
+    opt: &RunOpt,
+) -> Result<(), Box<dyn Error>> {
+    let prog = format::load(file, opt.format.format)?;
+    let diagnostics = prog.validate();
+    for diagnostic in &diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+    if diagnostics.iter().any(Diagnostic::is_error) {
+        return Err("The program has errors.".into());
+    }
+    if !diagnostics.is_empty() && !opt.allow_warnings {
+        return Err("The program has warnings; pass --allow-warnings to run it anyway.".into());
+    }
+    // The input alphabet is checked as the tape reads each symbol, so the input still streams.
+    let mut check = prog.input_checker();
+    let (graphemes, failure) =
+        until_error(graphemes.map(move |g| -> Result<_, Box<dyn Error>> { Ok(check(g?)?) }));
+
+    let blank = prog.blank();
+    // Transducers keep their scratch work on the tape and only print what they emitted.
+    let transducer = prog.is_transducer();
+
+    if prog.is_multitrack() {
+        let track_count = prog.track_count();
+        let tr_func = prog.into_multitrack()?;
+        // The input goes on the first track, which is also the one printed at the end.
+        let padding = blank.clone();
+        let to_cell = move |g| {
+            let mut tracks = vec![g];
+            tracks.resize(track_count, padding.clone());
+            Tracks::with_blank(tracks, padding.clone())
+        };
+        let tape = Streamed::with_blank(graphemes.map(to_cell.clone()), to_cell(blank.clone()));
+        let mut machine = TuringMachine::new(tr_func, tape);
+
+        let accept = run_reading(&mut machine, &failure, opt.debug, |m| {
+            m.tracks().to_string()
+        })?;
+        if transducer {
+            for item in machine.get_output() {
+                print!("{}", item.track(0));
+            }
+        } else {
+            let output = machine.get_tape().map(|cell| cell.track(0));
+            for item in trim_blanks(output, blank) {
+                print!("{}", item);
+            }
+            // Printing read the rest of the input.
+            failure.check()?;
+        }
+        println!("{}", accept);
+        return Ok(());
+    }
+
+    let tr_func = prog.into_single_track()?;
+    let tape = Streamed::with_blank(graphemes, blank.clone());
+    let mut machine = TuringMachine::new(tr_func, tape);
+
+    let accept = run_reading(&mut machine, &failure, opt.debug, |m| m.to_string())?;
+    if transducer {
+        for item in machine.get_output() {
+            print!("{}", item);
+        }
+    } else {
+        let output = machine.get_tape();
+        for item in trim_blanks(output, blank) {
+            print!("{}", item);
+        }
+        // Printing read the rest of the input.
+        failure.check()?;
+    }
+    println!("{}", accept);
+    Ok(())
+}
+
+fn run_busy_beaver(code: &str, debug: bool) -> Result<(), Box<dyn Error>> {
+    let prog = busy_beaver::parse(code)?.build(busy_beaver::INITIAL);
+    let tape = Unbounded::with_blank(busy_beaver::BLANK);
+    let mut machine = TuringMachine::new(prog, tape);
+
+    let accept = if debug {
         machine.run_debug()?
     } else {
         machine.run()
     };
-    let output = machine.get_tape();
-    for item in output {
+    for item in trim_blanks(machine.get_tape(), busy_beaver::BLANK) {
         print!("{}", item);
     }
     println!("{}", accept);
     Ok(())
 }
 
+fn compute(opt: ComputeOpt) -> Result<(), Box<dyn Error>> {
+    let prog = format::load(&opt.file, opt.format.format)?;
+    let blank = prog.blank();
+    let transducer = prog.is_transducer();
+    let input = opt.encoding.encode(&opt.args)?;
+    prog.check_input(&input)?;
+    let tr_func = prog.into_single_track()?;
+
+    let tape = Unbounded::from_input(input, blank.clone());
+    let mut machine = TuringMachine::new(tr_func, tape);
+    if !machine.run() {
+        return Err("The machine rejected its arguments.".into());
+    }
+
+    let result = if transducer {
+        opt.encoding
+            .decode(machine.get_output().iter().map(SmolStr::as_str))?
+    } else {
+        opt.encoding
+            .decode(trim_blanks(machine.get_tape(), blank))?
+    };
+    println!("{}", result);
+    Ok(())
+}
+
+fn enumerate(opt: EnumerateOpt) -> Result<(), Box<dyn Error>> {
+    let prog = format::load(&opt.file, opt.format.format)?;
+    if !prog.is_enumerator() {
+        return Err("An enumerator needs at least one print state.".into());
+    }
+    let blank = prog.blank();
+    let tr_func = prog.into_single_track()?;
+    let mut machine = TuringMachine::new(tr_func, Unbounded::with_blank(blank));
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let mut words = 0;
+    let mut steps = 0;
+    while opt.words.is_none_or(|limit| words < limit) && opt.steps.is_none_or(|limit| steps < limit)
+    {
+        let halted = machine.step().is_some();
+        steps += 1;
+        while let Some(word) = machine.take_emitted() {
+            for item in word {
+                write!(stdout, "{}", item)?;
+            }
+            writeln!(stdout)?;
+            stdout.flush()?;
+            words += 1;
+        }
+        if halted {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn convert(opt: ConvertOpt) -> Result<(), Box<dyn Error>> {
+    let prog = format::load(&opt.from, opt.format.format)?;
+    let converted = opt
+        .to_format
+        .unwrap_or_else(|| Format::from_path(&opt.to))
+        .write(&prog, opt.halt_anyway)?;
+    fs::write(&opt.to, converted)?;
+    Ok(())
+}
+
+fn decompile(opt: DecompileOpt) -> Result<(), Box<dyn Error>> {
+    let text = match (&opt.file, &opt.bb) {
+        (_, Some(code)) => {
+            let builder = busy_beaver::parse(code)?;
+            let program = post_turing::decompile(&builder, busy_beaver::INITIAL, opt.halt_anyway)?;
+            post_turing::emit(&program)
+        }
+        (Some(file), None) => {
+            post_turing::write(&format::load(file, opt.format.format)?, opt.halt_anyway)?
+        }
+        (None, None) => return Err("Give a program file or --bb.".into()),
+    };
+    fs::write(&opt.output, text)?;
+    Ok(())
+}
+
+fn batch(opt: BatchOpt) -> Result<(), Box<dyn Error>> {
+    let mut db = bbchallenge::Database::new(File::open(&opt.database)?)?;
+    let to = opt.to.unwrap_or_else(|| db.len());
+    if opt.from > to || to > db.len() {
+        return Err(format!(
+            "Can't run machines {} to {}; the database has {}.",
+            opt.from,
+            to,
+            db.len()
+        )
+        .into());
+    }
+    let mut out: Box<dyn Write> = match &opt.output {
+        Some(path) => Box::new(io::BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout()),
+    };
+    writeln!(out, "index,machine,outcome,steps")?;
+    for index in opt.from..to {
+        let builder = db.get(index)?;
+        let machine = busy_beaver::emit(&builder)?;
+        let (outcome, steps) = bbchallenge::decide(builder, opt.steps);
+        writeln!(out, "{},{},{},{}", index, machine, outcome, steps)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn reverse(opt: ReverseOpt) -> Result<(), Box<dyn Error>> {
+    let prog = format::load(&opt.file, opt.format.format)?;
+    let blank = prog.blank();
+    let (init, prog_builder) = prog.into_builder()?;
+    if prog_builder.has_wildcards() {
+        return Err("Wildcard transitions can't be checked for reversibility.".into());
+    }
+    if opt.check {
+        reversible::check(&prog_builder)?;
+        println!("Reversible.");
+        return Ok(());
+    }
+
+    let input = read_input(opt.input)?.collect::<io::Result<Vec<_>>>()?;
+    let tape = Unbounded::from_input(input, blank.clone());
+    let halted = Goto::Halt(!opt.reject);
+    let mut reverser = reversible::Reverser::new(&prog_builder, init, halted, tape, opt.head)?;
+    reverser.run(opt.steps)?;
+    for item in trim_blanks(reverser.get_tape(), blank) {
+        print!("{}", item);
+    }
+    println!();
+    Ok(())
+}
+
+fn read_input(path: Option<PathBuf>) -> io::Result<impl Iterator<Item = io::Result<SmolStr>>> {
+    let rdr: Box<dyn BufRead> = match path {
+        Some(path) => Box::new(BufReader::new(File::open(path)?)),
+        None => Box::new(BufReader::new(io::stdin())),
+    };
+    Ok(Graphemes::new(rdr))
+}
+
+/// Runs a machine whose input is read as it goes, stopping as soon as reading it fails.
+/// With `debug`, each step is shown with `view` and waits for enter, like `run_debug_with`.
+fn run_reading<S, A, T, P, E>(
+    machine: &mut TuringMachine<S, A, T, P>,
+    failure: &Failure<E>,
+    debug: bool,
+    view: impl Fn(&TuringMachine<S, A, T, P>) -> String,
+) -> Result<bool, Box<dyn Error>>
+where
+    A: Clone,
+    T: Tape<A>,
+    P: TransitionFn<S, A>,
+    E: Into<Box<dyn Error>>,
+{
+    let accept = machine.run_checked(|m| -> Result<(), Box<dyn Error>> {
+        failure.check().map_err(Into::into)?;
+        if debug {
+            println!("{}", view(m));
+            io::stdin().read_line(&mut String::new())?;
+        }
+        Ok(())
+    })?;
+    // The last step may have read the bad input.
+    failure.check().map_err(Into::into)?;
+    Ok(accept)
+}
+
 #[allow(dead_code)]
-fn printer() -> impl TransitionFn<SmolStr, SmolStr> {
-    |str_to_print: &SmolStr, current_symbol: &SmolStr| {
+fn printer(text: SmolStr) -> impl TransitionFn<SmolStr, SmolStr> {
+    (text, |str_to_print: &SmolStr, current_symbol: &SmolStr| {
         let mut chars = str_to_print.chars();
         let char_to_print = chars.next();
         let remainder = chars.collect::<SmolStr>();
         match char_to_print {
-            None => Response {
-                goto: Goto::Halt(true),
-                write: current_symbol.clone(),
-                mv: None,
-            },
-            Some(c) => Response {
-                goto: Goto::Run(remainder),
-                write: SmolStr::from(format!("{}", c)),
-                mv: Some(Movement::Right),
-            },
+            None => Response::new(Goto::Halt(true), current_symbol.clone(), None),
+            Some(c) => Response::new(
+                Goto::Run(remainder),
+                SmolStr::from(format!("{}", c)),
+                Some(Movement::Right),
+            ),
         }
-    }
+    })
 }
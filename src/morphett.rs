@@ -0,0 +1,344 @@
+use std::{collections::HashMap, error::Error, fmt};
+
+use crate::{
+    program::Movement,
+    program_ron::{ProgramFile, Rule},
+};
+
+/// The state every machine in this format starts in.
+pub const INITIAL: &str = "0";
+
+/// The symbol standing for a blank cell.
+pub const BLANK: &str = "_";
+
+/// Stands for any state or symbol, and when written leaves the symbol unchanged.
+pub const WILDCARD: &str = "*";
+
+/// A quintuple file we couldn't read, or a program the format can't express.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MorphettError(String);
+
+/// One line's rule, before wildcard states are expanded.
+struct Quintuple<'a> {
+    line: usize,
+    state: &'a str,
+    read: &'a str,
+    write: &'a str,
+    mv: Option<Movement>,
+    goto: &'a str,
+}
+
+/// Reads rules written `state symbol new_symbol direction new_state`, one per line, with
+/// `;` comments and an optional `!` breakpoint at the end, which is ignored.
+///
+/// The machine starts in `0` and `_` is the blank. A `*` reads any symbol, writes the symbol
+/// that was read, stays put, or keeps the same state. A `*` state stands for every state,
+/// for the symbols that state has no rule of its own for. States starting with `halt` halt,
+/// rejecting if named `halt-reject` and accepting otherwise; so does a missing rule, rejecting.
+pub fn read(text: &str) -> Result<ProgramFile, MorphettError> {
+    let mut quintuples = Vec::new();
+    let mut first_given = HashMap::new();
+    for (i, line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let err = |message: String| MorphettError(format!("Line {}: {}", line_no, message));
+        let code = line.split(';').next().unwrap_or_default();
+        let fields = code.split_whitespace().collect::<Vec<_>>();
+        let fields = match fields.as_slice() {
+            [] => continue,
+            [fields @ .., "!"] | fields => fields,
+        };
+        let (state, read, write, mv, goto) = match *fields {
+            [state, read, write, mv, goto] => (state, read, write, mv, goto),
+            _ => {
+                return Err(err(format!(
+                    "Expected `state symbol new_symbol direction new_state`, found {} fields.",
+                    fields.len()
+                )))
+            }
+        };
+        let mv = match mv {
+            "l" | "L" => Some(Movement::Left),
+            "r" | "R" => Some(Movement::Right),
+            "*" => None,
+            other => {
+                return Err(err(format!(
+                    "Unknown direction {:?}, expected l, r or *.",
+                    other
+                )))
+            }
+        };
+        if let Some(first) = first_given.insert((state, read), line_no) {
+            return Err(err(format!(
+                "State {:?} already has a rule for {:?}, on line {}.",
+                state, read, first
+            )));
+        }
+        quintuples.push(Quintuple {
+            line: line_no,
+            state,
+            read,
+            write,
+            mv,
+            goto,
+        });
+    }
+
+    let halts = |state: &str| state.starts_with("halt");
+    let mut states: Vec<&str> = vec![INITIAL];
+    let mut accept = Vec::new();
+    let mut reject = Vec::new();
+    for q in &quintuples {
+        for state in &[q.state, q.goto] {
+            if *state == WILDCARD || states.contains(state) {
+                continue;
+            }
+            states.push(state);
+            if *state == "halt-reject" {
+                reject.push(state.to_string());
+            } else if halts(state) {
+                accept.push(state.to_string());
+            }
+        }
+    }
+
+    let mut prog = ProgramFile::new(INITIAL, BLANK);
+    prog.set_halting(accept, reject);
+    prog.set_wildcard(WILDCARD);
+    let mut add = |q: &Quintuple<'_>, state: &str| {
+        let goto = if q.goto == WILDCARD { state } else { q.goto };
+        prog.add_rule(Rule {
+            state: state.to_owned(),
+            read: q.read.to_owned(),
+            goto: goto.to_owned(),
+            write: q.write.to_owned(),
+            mv: q.mv.clone(),
+        })
+        .map_err(|e| MorphettError(format!("Line {}: {}", q.line, e)))
+    };
+    for q in quintuples.iter().filter(|q| q.state != WILDCARD) {
+        add(q, q.state)?;
+    }
+    // A state's own rules come first, then `*` state rules for a symbol, then for `*`.
+    let mut wildcards = quintuples
+        .iter()
+        .filter(|q| q.state == WILDCARD)
+        .collect::<Vec<_>>();
+    wildcards.sort_by_key(|q| q.read == WILDCARD);
+    for q in wildcards {
+        for state in states.iter().filter(|state| !halts(state)) {
+            let covered = first_given.contains_key(&(*state, q.read))
+                || first_given.contains_key(&(*state, WILDCARD));
+            if !covered {
+                add(q, state)?;
+            }
+        }
+    }
+    Ok(prog)
+}
+
+/// Writes a program as quintuples. Accepting states become `halt-accept` unless their names
+/// already start with `halt`, and rejecting ones become `halt-reject`. A program starting
+/// anywhere but `0` gets a first rule moving into its initial state.
+pub fn write(prog: &ProgramFile) -> Result<String, MorphettError> {
+    let rules = prog.rules()?;
+    let blank = prog.blank();
+    let accepts = |state: &str| prog.accept_states().iter().any(|s| s == state);
+    let rejects = |state: &str| prog.reject_states().iter().any(|s| s == state);
+
+    let state_name = |state: &str| -> Result<String, MorphettError> {
+        if accepts(state) && state.starts_with("halt") && state != "halt-reject" {
+            return Ok(state.to_owned());
+        } else if accepts(state) {
+            return Ok("halt-accept".into());
+        } else if rejects(state) {
+            return Ok("halt-reject".into());
+        }
+        if state.starts_with("halt") {
+            return Err(MorphettError(format!(
+                "State {:?} would halt, since its name starts with \"halt\".",
+                state
+            )));
+        }
+        if state == WILDCARD || !is_token(state) {
+            return Err(MorphettError(format!(
+                "State {:?} can't be written as a single word.",
+                state
+            )));
+        }
+        Ok(state.to_owned())
+    };
+    let symbol = |symbol: &str| -> Result<String, MorphettError> {
+        if symbol == blank {
+            Ok(BLANK.into())
+        } else if symbol == BLANK || symbol == WILDCARD || !is_token(symbol) {
+            Err(MorphettError(format!(
+                "Symbol {:?} can't be written in this format.",
+                symbol
+            )))
+        } else {
+            Ok(symbol.to_owned())
+        }
+    };
+
+    let mut text = String::new();
+    if prog.initial() != INITIAL {
+        if rules
+            .iter()
+            .any(|rule| rule.state == INITIAL || rule.goto == INITIAL)
+        {
+            return Err(MorphettError(format!(
+                "The machine must start in state {:?}, which already means something else.",
+                INITIAL
+            )));
+        }
+        text.push_str(&format!(
+            "; Start in {}.\n{} * * * {}\n\n",
+            prog.initial(),
+            INITIAL,
+            state_name(prog.initial())?
+        ));
+    }
+    for rule in &rules {
+        let mv = match rule.mv {
+            Some(Movement::Left) => "l",
+            Some(Movement::Right) => "r",
+            None => "*",
+        };
+        text.push_str(&format!(
+            "{} {} {} {} {}\n",
+            state_name(&rule.state)?,
+            symbol(&rule.read)?,
+            symbol(&rule.write)?,
+            mv,
+            state_name(&rule.goto)?
+        ));
+    }
+    Ok(text)
+}
+
+/// Whether a name or symbol survives being split on whitespace and comments.
+fn is_token(s: &str) -> bool {
+    !s.is_empty() && s != "!" && !s.contains(';') && !s.contains(char::is_whitespace)
+}
+
+impl fmt::Display for MorphettError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for MorphettError {}
+
+impl From<ron::de::Error> for MorphettError {
+    fn from(e: ron::de::Error) -> Self {
+        MorphettError(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program::{Goto, TransitionFn};
+    use smol_str::SmolStr;
+
+    // Binary increment: walk to the end of the number, then carry back.
+    const INCREMENT: &str = "
+; Find the right end.
+0 * * r 0
+0 _ _ l carry
+
+carry 1 0 l carry   ; 1 plus a carry is 0 carrying 1
+carry 0 1 * halt
+carry _ 1 * halt !
+";
+
+    fn respond(prog: ProgramFile, state: &str, read: &str) -> (Goto<SmolStr>, SmolStr) {
+        let table = prog.into_single_track().expect("Single track.");
+        let response = table.respond(&SmolStr::from(state), &SmolStr::from(read));
+        (response.goto, response.write)
+    }
+
+    #[test]
+    fn import() {
+        let prog = read(INCREMENT).expect("Valid program.");
+        assert_eq!(prog.initial(), "0");
+        assert_eq!(prog.blank(), "_");
+        assert_eq!(prog.accept_states(), &["halt".to_string()]);
+        assert_eq!(
+            respond(prog.clone(), "0", "1"),
+            (Goto::Run(SmolStr::from("0")), SmolStr::from("1"))
+        );
+        assert_eq!(
+            respond(prog.clone(), "0", "_"),
+            (Goto::Run(SmolStr::from("carry")), SmolStr::from("_"))
+        );
+        assert_eq!(
+            respond(prog, "carry", "_"),
+            (Goto::Halt(true), SmolStr::from("1"))
+        );
+    }
+
+    #[test]
+    fn wildcard_states() {
+        let text = "
+0 a a r 1
+1 a b * halt-reject
+* a c r 0
+* * * r *
+";
+        let prog = read(text).expect("Valid program.");
+        assert_eq!(prog.reject_states(), &["halt-reject".to_string()]);
+        // A state's own rule wins over a `*` state rule.
+        assert_eq!(
+            respond(prog.clone(), "1", "a"),
+            (Goto::Halt(false), SmolStr::from("b"))
+        );
+        // A `*` state rule for the symbol wins over one for `*`.
+        assert_eq!(
+            respond(prog.clone(), "1", "b").0,
+            Goto::Run(SmolStr::from("1"))
+        );
+        assert_eq!(
+            respond(prog, "0", "b"),
+            (Goto::Run(SmolStr::from("0")), SmolStr::from("b"))
+        );
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let err = read("0 a a r 1\n\n0 a b l 1\n").unwrap_err();
+        assert!(err.to_string().starts_with("Line 3:"), "{}", err);
+        assert!(err.to_string().contains("line 1"), "{}", err);
+        let err = read("; fine\n0 a a x 1\n").unwrap_err();
+        assert!(err.to_string().starts_with("Line 2:"), "{}", err);
+        let err = read("0 a a r\n").unwrap_err();
+        assert!(err.to_string().starts_with("Line 1:"), "{}", err);
+    }
+
+    #[test]
+    fn round_trip() {
+        let prog = read(INCREMENT).expect("Valid program.");
+        let text = write(&prog).expect("Exportable.");
+        let again = read(&text).expect("Exports a valid program.");
+        assert_eq!(again.rules(), prog.rules());
+        assert_eq!(again.accept_states(), prog.accept_states());
+    }
+
+    #[test]
+    fn export_renames() {
+        let mut prog = ProgramFile::new("start", " ");
+        prog.add_rule(Rule {
+            state: "start".into(),
+            read: " ".into(),
+            goto: "accept".into(),
+            write: "x".into(),
+            mv: None,
+        })
+        .expect("First transition.");
+        let text = write(&prog).expect("Exportable.");
+        assert_eq!(
+            text,
+            "; Start in start.\n0 * * * start\n\nstart _ x * halt-accept\n"
+        );
+    }
+}
@@ -0,0 +1,417 @@
+use std::{collections::HashMap, error::Error, fmt, fmt::Debug, hash::Hash};
+
+use crate::program::{Goto, Response, Stimulus, TransitionFn};
+
+/// A tape cell made up of several tracks.
+/// Tracks past the end of the vector hold the cell's blank, so `Tracks::with_blank(vec![], b)`
+/// is a blank cell with any number of tracks.
+#[derive(Debug, Clone, Default)]
+pub struct Tracks<Alphabet> {
+    symbols: Vec<Alphabet>,
+    blank: Alphabet,
+}
+
+/// A per-track pattern. `None` matches any symbol when reading and leaves the track unchanged
+/// when writing.
+pub type TrackPattern<Alphabet> = Vec<Option<Alphabet>>;
+
+impl<Alphabet> Tracks<Alphabet> {
+    pub fn with_blank(symbols: Vec<Alphabet>, blank: Alphabet) -> Self {
+        Self { symbols, blank }
+    }
+
+    pub fn get(&self, track: usize) -> Option<&Alphabet> {
+        self.symbols.get(track)
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+}
+
+impl<Alphabet> Tracks<Alphabet>
+where
+    Alphabet: Clone,
+{
+    /// Returns the symbol on the given track, blank if the track was never written.
+    pub fn track(&self, track: usize) -> Alphabet {
+        self.get(track).unwrap_or(&self.blank).clone()
+    }
+
+    pub fn set(&mut self, track: usize, symbol: Alphabet) {
+        if self.symbols.len() <= track {
+            self.symbols.resize(track + 1, self.blank.clone());
+        }
+        self.symbols[track] = symbol;
+    }
+
+    fn matches(&self, pattern: &[Option<Alphabet>]) -> bool
+    where
+        Alphabet: PartialEq,
+    {
+        pattern.iter().enumerate().all(|(i, p)| match p {
+            Some(symbol) => self.track(i) == *symbol,
+            None => true,
+        })
+    }
+
+    fn overwrite(&mut self, pattern: &[Option<Alphabet>]) {
+        for (i, p) in pattern.iter().enumerate() {
+            if let Some(symbol) = p {
+                self.set(i, symbol.clone());
+            }
+        }
+    }
+}
+
+impl<Alphabet> PartialEq for Tracks<Alphabet>
+where
+    Alphabet: Clone + PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        let len = self.len().max(other.len());
+        (0..len).all(|i| self.track(i) == other.track(i))
+    }
+}
+
+impl<Alphabet> Eq for Tracks<Alphabet> where Alphabet: Clone + Eq {}
+
+impl<Alphabet> From<Vec<Alphabet>> for Tracks<Alphabet>
+where
+    Alphabet: Default,
+{
+    fn from(tracks: Vec<Alphabet>) -> Self {
+        Self::with_blank(tracks, Default::default())
+    }
+}
+
+/// How many tracks a pattern pins to a symbol. Tracks past its end are wildcards too.
+fn specificity<Alphabet>(pattern: &[Option<Alphabet>]) -> usize {
+    pattern.iter().filter(|p| p.is_some()).count()
+}
+
+/// The cells two patterns both match, if there are any.
+fn intersect<Alphabet>(
+    a: &[Option<Alphabet>],
+    b: &[Option<Alphabet>],
+) -> Option<TrackPattern<Alphabet>>
+where
+    Alphabet: Clone + PartialEq,
+{
+    (0..a.len().max(b.len()))
+        .map(
+            |i| match (a.get(i).cloned().flatten(), b.get(i).cloned().flatten()) {
+                (Some(x), Some(y)) if x != y => Err(()),
+                (Some(x), _) | (None, Some(x)) => Ok(Some(x)),
+                (None, None) => Ok(None),
+            },
+        )
+        .collect::<Result<_, _>>()
+        .ok()
+}
+
+/// Whether every cell `specific` matches is also matched by `general`.
+fn covers<Alphabet>(general: &[Option<Alphabet>], specific: &[Option<Alphabet>]) -> bool
+where
+    Alphabet: PartialEq,
+{
+    general.iter().enumerate().all(|(i, p)| match p {
+        Some(symbol) => matches!(specific.get(i), Some(Some(s)) if s == symbol),
+        None => true,
+    })
+}
+
+/// Two equally specific patterns from the same state that both match some cell, with no more
+/// specific pattern to settle which applies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AmbiguousPatterns<State, Alphabet> {
+    pub state: State,
+    pub first: TrackPattern<Alphabet>,
+    pub second: TrackPattern<Alphabet>,
+}
+
+impl<State, Alphabet> fmt::Display for AmbiguousPatterns<State, Alphabet>
+where
+    State: Debug,
+    Alphabet: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |pattern: &TrackPattern<Alphabet>| {
+            let tracks = pattern.iter().map(|p| match p {
+                Some(symbol) => format!("{:?}", symbol),
+                // Programs name their wildcard as they like, so it isn't shown by name.
+                None => "any".to_owned(),
+            });
+            format!("({})", tracks.collect::<Vec<_>>().join(", "))
+        };
+        write!(
+            f,
+            "State {:?} has patterns {} and {} that both match some cells, and neither is more \
+             specific.",
+            self.state,
+            show(&self.first),
+            show(&self.second)
+        )
+    }
+}
+
+impl<State, Alphabet> Error for AmbiguousPatterns<State, Alphabet>
+where
+    State: Debug,
+    Alphabet: Debug,
+{
+}
+
+type TrackRule<State, Alphabet> = (
+    TrackPattern<Alphabet>,
+    Response<State, TrackPattern<Alphabet>>,
+);
+
+#[derive(Debug, Clone)]
+pub struct MultiTrackBuilder<State, Alphabet>
+where
+    State: Hash + Eq,
+{
+    table: HashMap<State, Vec<TrackRule<State, Alphabet>>>,
+}
+
+impl<State, Alphabet> Default for MultiTrackBuilder<State, Alphabet>
+where
+    State: Hash + Eq,
+{
+    fn default() -> Self {
+        Self {
+            table: HashMap::new(),
+        }
+    }
+}
+
+impl<State, Alphabet> MultiTrackBuilder<State, Alphabet>
+where
+    State: Hash + Eq + Clone,
+    Alphabet: Clone + Default + PartialEq,
+{
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// When several patterns match a cell, the one with the fewest wildcards wins.
+    /// Equally specific patterns that overlap are an error, unless a more specific pattern
+    /// covers every cell they share.
+    pub fn build(
+        self,
+        initial: State,
+    ) -> Result<MultiTrackProgram<State, Alphabet>, AmbiguousPatterns<State, Alphabet>> {
+        for (state, rules) in &self.table {
+            for (i, (first, _)) in rules.iter().enumerate() {
+                for (second, _) in &rules[i + 1..] {
+                    let level = specificity(first);
+                    if specificity(second) != level {
+                        continue;
+                    }
+                    let shared = match intersect(first, second) {
+                        Some(shared) => shared,
+                        None => continue,
+                    };
+                    let settled = rules.iter().any(|(pattern, _)| {
+                        specificity(pattern) > level && covers(pattern, &shared)
+                    });
+                    if !settled {
+                        return Err(AmbiguousPatterns {
+                            state: state.clone(),
+                            first: first.clone(),
+                            second: second.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(MultiTrackProgram {
+            initial,
+            table: self.table,
+        })
+    }
+
+    pub fn add_transition<S, R>(&mut self, stimulus: S, response: R)
+    where
+        Stimulus<State, TrackPattern<Alphabet>>: From<S>,
+        Response<State, TrackPattern<Alphabet>>: From<R>,
+    {
+        let stimulus: Stimulus<_, _> = stimulus.into();
+        let response = response.into();
+        self.table
+            .entry(stimulus.state)
+            .or_default()
+            .push((stimulus.read, response));
+    }
+
+    pub fn with_transition<S, R>(mut self, stimulus: S, response: R) -> Self
+    where
+        Stimulus<State, TrackPattern<Alphabet>>: From<S>,
+        Response<State, TrackPattern<Alphabet>>: From<R>,
+    {
+        self.add_transition(stimulus, response);
+        self
+    }
+}
+
+/// A built multi-track program, matching each cell against its state's patterns.
+#[derive(Debug, Clone)]
+pub struct MultiTrackProgram<State, Alphabet> {
+    initial: State,
+    table: HashMap<State, Vec<TrackRule<State, Alphabet>>>,
+}
+
+impl<State, Alphabet> TransitionFn<State, Tracks<Alphabet>> for MultiTrackProgram<State, Alphabet>
+where
+    State: Hash + Eq + Clone,
+    Alphabet: Clone + PartialEq,
+{
+    fn initial(&self) -> &State {
+        &self.initial
+    }
+
+    fn respond(&self, state: &State, cell: &Tracks<Alphabet>) -> Response<State, Tracks<Alphabet>> {
+        let best = self
+            .table
+            .get(state)
+            .into_iter()
+            .flatten()
+            .filter(|(pattern, _)| cell.matches(pattern))
+            .max_by_key(|(pattern, _)| specificity(pattern));
+        match best {
+            Some((_, response)) => {
+                // Wildcards write, and output, whatever the cell held on that track.
+                let resolve = |pattern: &TrackPattern<Alphabet>| {
+                    let mut resolved = cell.clone();
+                    resolved.overwrite(pattern);
+                    resolved
+                };
+                Response {
+                    goto: response.goto.clone(),
+                    write: resolve(&response.write),
+                    mv: response.mv.clone(),
+                    output: response.output.as_ref().map(resolve),
+                }
+            }
+            None => Response::new(Goto::Halt(false), cell.clone(), None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program::Movement;
+
+    #[test]
+    fn blank_tracks_are_equal() {
+        assert_eq!(Tracks::<u8>::default(), Tracks::from(vec![0, 0]));
+        assert_ne!(Tracks::<u8>::default(), Tracks::from(vec![0, 1]));
+    }
+
+    #[test]
+    fn wildcards() {
+        let prog = MultiTrackBuilder::new()
+            .with_transition(
+                (0, vec![Some(1), None]),
+                (Goto::Run(1), vec![None, Some(9)], Some(Movement::Right)),
+            )
+            .with_transition(
+                (0, vec![Some(1), Some(2)]),
+                (Goto::Halt(true), vec![Some(5)], None),
+            )
+            .build(0)
+            .expect("The more specific pattern wins.");
+        assert_eq!(
+            prog.respond(&0, &Tracks::from(vec![1, 3])),
+            Response::new(
+                Goto::Run(1),
+                Tracks::from(vec![1, 9]),
+                Some(Movement::Right)
+            )
+        );
+        assert_eq!(
+            prog.respond(&0, &Tracks::from(vec![1, 2])),
+            Response::new(Goto::Halt(true), Tracks::from(vec![5, 2]), None)
+        );
+        assert_eq!(
+            prog.respond(&0, &Tracks::from(vec![2])),
+            Response::new(Goto::Halt(false), Tracks::from(vec![2]), None)
+        );
+    }
+
+    #[test]
+    fn output_wildcards_copy_the_cell() {
+        let prog = MultiTrackBuilder::new()
+            .with_transition(
+                (0, vec![None, None]),
+                (Goto::Halt(true), vec![], None, Some(vec![None, Some(4)])),
+            )
+            .build(0)
+            .expect("A single pattern.");
+        let output = prog.respond(&0, &Tracks::from(vec![3, 8])).output;
+        assert_eq!(output, Some(Tracks::from(vec![3, 4])));
+    }
+
+    #[test]
+    fn ambiguous_patterns() {
+        let overlapping = || {
+            MultiTrackBuilder::new()
+                .with_transition((0, vec![Some(0), None]), (Goto::Halt(true), vec![], None))
+                .with_transition((0, vec![None, Some(7)]), (Goto::Halt(false), vec![], None))
+        };
+        let err = overlapping().build(0).expect_err("Both match (0, 7).");
+        assert_eq!(err.state, 0);
+        let message = err.to_string();
+        assert!(
+            message.contains("(0, any)") && message.contains("(any, 7)"),
+            "{}",
+            message
+        );
+
+        // A pattern for exactly the shared cells settles it.
+        let prog = overlapping()
+            .with_transition((0, vec![Some(0), Some(7)]), (Goto::Run(1), vec![], None))
+            .build(0)
+            .expect("Settled by the specific pattern.");
+        assert_eq!(
+            prog.respond(&0, &Tracks::from(vec![0, 7])).goto,
+            Goto::Run(1)
+        );
+        assert_eq!(
+            prog.respond(&0, &Tracks::from(vec![0, 3])).goto,
+            Goto::Halt(true)
+        );
+
+        // Patterns that never match the same cell don't conflict.
+        let disjoint = MultiTrackBuilder::new()
+            .with_transition((0, vec![Some(0), None]), (Goto::Halt(true), vec![], None))
+            .with_transition((0, vec![Some(1), None]), (Goto::Halt(false), vec![], None))
+            .build(0);
+        assert!(disjoint.is_ok());
+    }
+
+    #[test]
+    fn missing_tracks_are_the_declared_blank() {
+        let cell = Tracks::with_blank(vec!['a'], '_');
+        assert_eq!(cell.track(2), '_');
+        let mut cell = cell;
+        cell.set(2, 'c');
+        assert_eq!(cell.get(1), Some(&'_'));
+        let prog = MultiTrackBuilder::new()
+            .with_transition(
+                (0, vec![Some('a'), Some('_')]),
+                (Goto::Halt(true), vec![], None),
+            )
+            .build(0)
+            .expect("A single pattern.");
+        let cell = Tracks::with_blank(vec!['a'], '_');
+        assert_eq!(prog.respond(&0, &cell).goto, Goto::Halt(true));
+    }
+}
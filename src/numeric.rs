@@ -0,0 +1,204 @@
+use smol_str::SmolStr;
+
+use std::{borrow::Borrow, error::Error, fmt, str::FromStr};
+
+/// How integers are written onto and read back from the tape.
+/// Unary arguments are separated by `0`; binary and decimal ones by `#`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Unary,
+    Binary,
+    Decimal,
+}
+
+pub const UNARY_SEPARATOR: &str = "0";
+pub const SEPARATOR: &str = "#";
+
+/// The largest argument written in unary, which takes a cell for every unit.
+pub const UNARY_LIMIT: u64 = 1_000_000;
+
+/// An argument too large to write on the tape in the chosen encoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodeError {
+    pub arg: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// A symbol that isn't a digit in the chosen encoding, at the given cell of the trimmed tape.
+    UnexpectedSymbol {
+        position: usize,
+        symbol: SmolStr,
+    },
+    /// Binary and decimal numbers need at least one digit.
+    Empty,
+    Overflow,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseEncodingError(String);
+
+impl Encoding {
+    pub fn encode(self, args: &[u64]) -> Result<Vec<SmolStr>, EncodeError> {
+        let separator = match self {
+            Encoding::Unary => UNARY_SEPARATOR,
+            Encoding::Binary | Encoding::Decimal => SEPARATOR,
+        };
+        let mut tape = Vec::new();
+        for (i, &n) in args.iter().enumerate() {
+            if i > 0 {
+                tape.push(SmolStr::from(separator));
+            }
+            let digits = match self {
+                Encoding::Unary if n > UNARY_LIMIT => return Err(EncodeError { arg: n }),
+                Encoding::Unary => "1".repeat(n as usize),
+                Encoding::Binary => format!("{:b}", n),
+                Encoding::Decimal => format!("{}", n),
+            };
+            tape.extend(digits.chars().map(|c| SmolStr::from(c.to_string())));
+        }
+        Ok(tape)
+    }
+
+    /// Reads a single number off a tape whose surrounding blanks have already been trimmed.
+    pub fn decode<I, S>(self, tape: I) -> Result<u64, DecodeError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Borrow<str>,
+    {
+        let radix = match self {
+            Encoding::Unary => 1,
+            Encoding::Binary => 2,
+            Encoding::Decimal => 10,
+        };
+        let mut value: Option<u64> = None;
+        for (position, symbol) in tape.into_iter().enumerate() {
+            let symbol = symbol.borrow();
+            let digit = match (self, symbol) {
+                (Encoding::Unary, "1") => Some(1),
+                (Encoding::Unary, _) => None,
+                (_, s) => s
+                    .parse::<char>()
+                    .ok()
+                    .and_then(|c| c.to_digit(radix))
+                    .map(u64::from),
+            }
+            .ok_or_else(|| DecodeError::UnexpectedSymbol {
+                position,
+                symbol: SmolStr::from(symbol),
+            })?;
+            let acc = value.unwrap_or(0);
+            let next = match self {
+                Encoding::Unary => acc.checked_add(digit),
+                _ => acc
+                    .checked_mul(u64::from(radix))
+                    .and_then(|acc| acc.checked_add(digit)),
+            };
+            value = Some(next.ok_or(DecodeError::Overflow)?);
+        }
+        match (self, value) {
+            (Encoding::Unary, None) => Ok(0),
+            (_, None) => Err(DecodeError::Empty),
+            (_, Some(n)) => Ok(n),
+        }
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = ParseEncodingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unary" => Ok(Encoding::Unary),
+            "binary" => Ok(Encoding::Binary),
+            "decimal" => Ok(Encoding::Decimal),
+            _ => Err(ParseEncodingError(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Argument {} is too large to write in unary, which allows up to {}.",
+            self.arg, UNARY_LIMIT
+        )
+    }
+}
+
+impl Error for EncodeError {}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnexpectedSymbol { position, symbol } => write!(
+                f,
+                "Malformed result: unexpected symbol {:?} at cell {}.",
+                symbol, position
+            ),
+            DecodeError::Empty => write!(f, "Malformed result: the tape is empty."),
+            DecodeError::Overflow => write!(f, "Result is too large to represent."),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+impl fmt::Display for ParseEncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Unknown encoding {:?}, expected unary, binary or decimal.",
+            self.0
+        )
+    }
+}
+
+impl Error for ParseEncodingError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn concat(tape: Result<Vec<SmolStr>, EncodeError>) -> String {
+        let tape = tape.expect("Small enough to encode.");
+        tape.iter().map(SmolStr::as_str).collect()
+    }
+
+    #[test]
+    fn encode() {
+        assert_eq!(concat(Encoding::Unary.encode(&[3, 0, 2])), "1110011");
+        assert_eq!(concat(Encoding::Binary.encode(&[5, 0])), "101#0");
+        assert_eq!(concat(Encoding::Decimal.encode(&[42, 7])), "42#7");
+        assert_eq!(
+            Encoding::Unary.encode(&[1, u64::MAX]),
+            Err(EncodeError { arg: u64::MAX })
+        );
+        let large = concat(Encoding::Decimal.encode(&[u64::MAX]));
+        assert_eq!(large, u64::MAX.to_string());
+    }
+
+    #[test]
+    fn decode() {
+        assert_eq!(Encoding::Unary.decode(vec!["1"; 7]), Ok(7));
+        assert_eq!(Encoding::Unary.decode(Vec::<&str>::new()), Ok(0));
+        assert_eq!(Encoding::Binary.decode(vec!["1", "1", "0"]), Ok(6));
+        assert_eq!(Encoding::Decimal.decode(vec!["4", "2"]), Ok(42));
+        assert_eq!(
+            Encoding::Decimal.decode(Vec::<&str>::new()),
+            Err(DecodeError::Empty)
+        );
+        assert_eq!(
+            Encoding::Binary.decode(vec!["1", "2"]),
+            Err(DecodeError::UnexpectedSymbol {
+                position: 1,
+                symbol: SmolStr::from("2"),
+            })
+        );
+        assert_eq!(
+            Encoding::Decimal.decode(vec!["9"; 30]),
+            Err(DecodeError::Overflow)
+        );
+    }
+}
@@ -0,0 +1,511 @@
+use std::{collections::HashMap, error::Error, fmt, iter};
+
+use crate::{
+    program::{Goto, Movement, ProgramBuilder, Response},
+    program_ron::{ProgramFile, Rule},
+};
+
+/// Programs start at their first instruction.
+pub const INITIAL: usize = 1;
+
+pub const BLANK: usize = 0;
+pub const MARK: usize = 1;
+
+/// A numbered list we couldn't read, or a machine it can't express.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PostTuringError(String);
+
+/// An instruction of a Post–Turing program, jumping to instructions by number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Mark,
+    Erase,
+    Left,
+    Right,
+    Jump(usize),
+    JumpIfMarked(usize),
+    JumpIfBlank(usize),
+    Halt,
+}
+
+/// Reads one instruction per line, numbered from 1 in order, such as `3 JUMP-IF-MARKED 1`,
+/// with `;` comments.
+pub fn parse(text: &str) -> Result<Vec<Instruction>, PostTuringError> {
+    let mut program = Vec::new();
+    let mut lines = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let err = |message: String| PostTuringError(format!("Line {}: {}", line_no, message));
+        let code = line.split(';').next().unwrap_or_default();
+        let words = code.split_whitespace().collect::<Vec<_>>();
+        let (number, words) = match words.split_first() {
+            None => continue,
+            Some((number, words)) => (number.trim_end_matches(['.', ':']), words),
+        };
+        let expected = program.len() + 1;
+        if number.parse() != Ok(expected) {
+            return Err(err(format!(
+                "Expected instruction number {}, found {:?}.",
+                expected, number
+            )));
+        }
+        let target = |arg: Option<&&str>| {
+            arg.and_then(|arg| arg.parse().ok())
+                .ok_or_else(|| err("The jump needs an instruction number.".into()))
+        };
+        let name = words.first().map(|word| word.to_ascii_uppercase());
+        let instruction = match (name.as_deref(), words.len()) {
+            (Some("MARK"), 1) => Instruction::Mark,
+            (Some("ERASE"), 1) => Instruction::Erase,
+            (Some("LEFT"), 1) => Instruction::Left,
+            (Some("RIGHT"), 1) => Instruction::Right,
+            (Some("HALT"), 1) => Instruction::Halt,
+            (Some("JUMP"), 2) => Instruction::Jump(target(words.get(1))?),
+            (Some("JUMP-IF-MARKED"), 2) => Instruction::JumpIfMarked(target(words.get(1))?),
+            (Some("JUMP-IF-BLANK"), 2) => Instruction::JumpIfBlank(target(words.get(1))?),
+            _ => {
+                return Err(err(format!(
+                    "Unknown instruction {:?}, expected MARK, ERASE, LEFT, RIGHT, JUMP n, \
+                     JUMP-IF-MARKED n, JUMP-IF-BLANK n or HALT.",
+                    words.join(" ")
+                )))
+            }
+        };
+        program.push(instruction);
+        lines.push(line_no);
+    }
+
+    for (instruction, line) in program.iter().zip(lines) {
+        if let Some(n) = instruction.target() {
+            if n == 0 || n > program.len() {
+                return Err(PostTuringError(format!(
+                    "Line {}: There is no instruction {} to jump to.",
+                    line, n
+                )));
+            }
+        }
+    }
+    Ok(program)
+}
+
+/// Writes a program back out, one numbered instruction per line.
+pub fn emit(program: &[Instruction]) -> String {
+    program
+        .iter()
+        .enumerate()
+        .map(|(i, instruction)| format!("{} {}\n", i + 1, instruction))
+        .collect()
+}
+
+/// Reads a program into a table whose states are instruction numbers, over `0` for blanks and
+/// `1` for marks, as `compile` does.
+pub fn read(text: &str) -> Result<ProgramFile, PostTuringError> {
+    let builder = compile(&parse(text)?);
+    let mut prog = ProgramFile::new(INITIAL.to_string(), BLANK.to_string());
+    for (state, read, response) in builder.transitions() {
+        let goto = match &response.goto {
+            Goto::Run(next) | Goto::Emit(next) => next.to_string(),
+            // Programs only halt by accepting.
+            Goto::Halt(_) => "accept".into(),
+        };
+        prog.add_rule(Rule {
+            state: state.to_string(),
+            read: read.to_string(),
+            goto,
+            write: response.write.to_string(),
+            mv: response.mv.clone(),
+        })?;
+    }
+    Ok(prog)
+}
+
+/// Writes any program over its blank and one other symbol, which becomes the mark, by way of
+/// `decompile`. Unless `halt_anyway` is set, a program that rejects or has a missing transition
+/// is refused, since the written program would halt there and so accept.
+pub fn write(prog: &ProgramFile, halt_anyway: bool) -> Result<String, PostTuringError> {
+    let blank = prog.blank();
+    let mut mark = None;
+    let mut symbol = |symbol: &str| {
+        if symbol == blank {
+            return Ok(BLANK);
+        }
+        match &mark {
+            None => mark = Some(symbol.to_owned()),
+            Some(mark) if mark == symbol => {}
+            Some(mark) => {
+                return Err(PostTuringError(format!(
+                    "The program uses {:?} and {:?} besides its blank, but a Post–Turing \
+                     program only has blanks and marks.",
+                    mark, symbol
+                )))
+            }
+        }
+        Ok(MARK)
+    };
+    let mut states = vec![prog.initial().to_owned()];
+    let mut state = |name: &str| match states.iter().position(|state| state == name) {
+        Some(number) => number,
+        None => {
+            states.push(name.to_owned());
+            states.len() - 1
+        }
+    };
+    let rules = prog.rules()?;
+    let mut builder = ProgramBuilder::new();
+    for rule in &rules {
+        let goto = if prog.accept_states().contains(&rule.goto) {
+            Goto::Halt(true)
+        } else if prog.reject_states().contains(&rule.goto) {
+            if !halt_anyway {
+                return Err(PostTuringError(format!(
+                    "{:?} reading {:?} rejects, but a Post–Turing program can only halt.",
+                    rule.state, rule.read
+                )));
+            }
+            Goto::Halt(false)
+        } else {
+            Goto::Run(state(&rule.goto))
+        };
+        builder.add_transition(
+            (state(&rule.state), symbol(&rule.read)?),
+            (goto, symbol(&rule.write)?, rule.mv.clone()),
+        );
+    }
+    if !halt_anyway {
+        let symbols = iter::once(blank.as_str()).chain(mark.as_deref());
+        for read in symbols {
+            let missing = states.iter().find(|&name| {
+                !rules
+                    .iter()
+                    .any(|rule| rule.state == *name && rule.read == read)
+            });
+            if let Some(name) = missing {
+                return Err(PostTuringError(format!(
+                    "{:?} has no transition reading {:?}, where a Post–Turing program would \
+                     halt and so accept.",
+                    name, read
+                )));
+            }
+        }
+    }
+    Ok(emit(&decompile(&builder, 0, true)?))
+}
+
+/// Compiles a program into a table whose states are instruction numbers, starting in `INITIAL`.
+/// Each instruction takes one step, and running past the last one halts like `HALT`.
+pub fn compile(program: &[Instruction]) -> ProgramBuilder<usize, usize> {
+    let mut builder = ProgramBuilder::new();
+    let goto = |n: usize| {
+        if n > program.len() {
+            Goto::Halt(true)
+        } else {
+            Goto::Run(n)
+        }
+    };
+    for (i, instruction) in program.iter().enumerate() {
+        let state = i + 1;
+        let next = goto(state + 1);
+        for &read in &[BLANK, MARK] {
+            let (goto, write, mv) = match *instruction {
+                Instruction::Mark => (next.clone(), MARK, None),
+                Instruction::Erase => (next.clone(), BLANK, None),
+                Instruction::Left => (next.clone(), read, Some(Movement::Left)),
+                Instruction::Right => (next.clone(), read, Some(Movement::Right)),
+                Instruction::Jump(n) => (goto(n), read, None),
+                Instruction::JumpIfMarked(n) if read == MARK => (goto(n), read, None),
+                Instruction::JumpIfBlank(n) if read == BLANK => (goto(n), read, None),
+                Instruction::JumpIfMarked(_) | Instruction::JumpIfBlank(_) => {
+                    (next.clone(), read, None)
+                }
+                Instruction::Halt => (Goto::Halt(true), read, None),
+            };
+            builder.add_transition((state, read), (goto, write, mv));
+        }
+    }
+    builder
+}
+
+/// Where a decompiled instruction jumps, before instructions have numbers.
+#[derive(Clone, Copy)]
+enum Label {
+    /// The block for a state of the machine.
+    State(usize),
+    /// The part of a state's block for reading a mark.
+    Marked(usize),
+}
+
+/// Turns a machine over blanks and marks into a program, a block of instructions per state
+/// starting with `initial`. The program can only halt, so rejecting and missing transitions
+/// are refused, unless `halt_anyway` lets them become `HALT` too.
+pub fn decompile(
+    builder: &ProgramBuilder<usize, usize>,
+    initial: usize,
+    halt_anyway: bool,
+) -> Result<Vec<Instruction>, PostTuringError> {
+    if builder.has_wildcards() {
+        return Err(PostTuringError(
+            "Wildcard transitions can't be decompiled.".into(),
+        ));
+    }
+    let mut table = HashMap::new();
+    let mut states = vec![initial];
+    for (&state, &read, response) in builder.transitions() {
+        if read > MARK || response.write > MARK {
+            return Err(PostTuringError(format!(
+                "State {} uses symbols other than {} and {}.",
+                state, BLANK, MARK
+            )));
+        }
+        if response.goto == Goto::Halt(false) && !halt_anyway {
+            return Err(PostTuringError(format!(
+                "State {} reading {} rejects, but a program can only halt.",
+                state, read
+            )));
+        }
+        table.insert((state, read), response);
+        states.push(state);
+        if let Goto::Run(next) = response.goto {
+            states.push(next);
+        }
+    }
+    states[1..].sort();
+    states.dedup();
+    let initial_again = states[1..].iter().position(|&state| state == initial);
+    if let Some(i) = initial_again {
+        states.remove(i + 1);
+    }
+
+    // Lay out each state's block with symbolic jumps, then number them.
+    let mut program: Vec<(Instruction, Option<Label>)> = Vec::new();
+    let mut addresses = HashMap::new();
+    for &state in &states {
+        let start = program.len() + 1;
+        addresses.insert(("state", state), start);
+        program.push((Instruction::JumpIfMarked(0), Some(Label::Marked(state))));
+        for &read in &[BLANK, MARK] {
+            if read == MARK {
+                addresses.insert(("marked", state), program.len() + 1);
+            }
+            match table.get(&(state, read)) {
+                None if !halt_anyway => {
+                    return Err(PostTuringError(format!(
+                        "State {} has no transition reading {}, where a program would halt and \
+                         so accept.",
+                        state, read
+                    )))
+                }
+                None => program.push((Instruction::Halt, None)),
+                Some(response) => program.extend(block(state, read, response)?),
+            }
+        }
+    }
+    let address = |label: Label| match label {
+        Label::State(state) => addresses[&("state", state)],
+        Label::Marked(state) => addresses[&("marked", state)],
+    };
+    Ok(program
+        .into_iter()
+        .map(|(instruction, label)| match (instruction, label) {
+            (Instruction::Jump(_), Some(label)) => Instruction::Jump(address(label)),
+            (Instruction::JumpIfMarked(_), Some(label)) => {
+                Instruction::JumpIfMarked(address(label))
+            }
+            (instruction, _) => instruction,
+        })
+        .collect())
+}
+
+/// The instructions carrying out one transition.
+fn block(
+    state: usize,
+    read: usize,
+    response: &Response<usize, usize>,
+) -> Result<Vec<(Instruction, Option<Label>)>, PostTuringError> {
+    let mut block = Vec::new();
+    if response.write != read {
+        let write = if response.write == MARK {
+            Instruction::Mark
+        } else {
+            Instruction::Erase
+        };
+        block.push((write, None));
+    }
+    match response.mv {
+        Some(Movement::Left) => block.push((Instruction::Left, None)),
+        Some(Movement::Right) => block.push((Instruction::Right, None)),
+        None => {}
+    }
+    match &response.goto {
+        Goto::Run(next) => block.push((Instruction::Jump(0), Some(Label::State(*next)))),
+        Goto::Halt(_) => block.push((Instruction::Halt, None)),
+        Goto::Emit(_) => {
+            return Err(PostTuringError(format!(
+                "State {} reading {} prints a word, which a program can't express.",
+                state, read
+            )))
+        }
+    }
+    Ok(block)
+}
+
+impl Instruction {
+    fn target(&self) -> Option<usize> {
+        match *self {
+            Instruction::Jump(n) | Instruction::JumpIfMarked(n) | Instruction::JumpIfBlank(n) => {
+                Some(n)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Mark => write!(f, "MARK"),
+            Instruction::Erase => write!(f, "ERASE"),
+            Instruction::Left => write!(f, "LEFT"),
+            Instruction::Right => write!(f, "RIGHT"),
+            Instruction::Jump(n) => write!(f, "JUMP {}", n),
+            Instruction::JumpIfMarked(n) => write!(f, "JUMP-IF-MARKED {}", n),
+            Instruction::JumpIfBlank(n) => write!(f, "JUMP-IF-BLANK {}", n),
+            Instruction::Halt => write!(f, "HALT"),
+        }
+    }
+}
+
+impl fmt::Display for PostTuringError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for PostTuringError {}
+
+impl From<ron::de::Error> for PostTuringError {
+    fn from(e: ron::de::Error) -> Self {
+        PostTuringError(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        busy_beaver, morphett,
+        tape::{trim_blanks, Unbounded},
+        turing_machine::TuringMachine,
+    };
+    use smol_str::SmolStr;
+
+    // Appends a mark to a block of marks: walk right past them, then mark the first blank.
+    const SUCCESSOR: &str = "
+1 JUMP-IF-BLANK 4   ; found the end
+2 RIGHT
+3 JUMP 1
+4 MARK
+5 HALT
+";
+
+    fn run(builder: ProgramBuilder<usize, usize>, initial: usize, input: Vec<usize>) -> Vec<usize> {
+        let prog = builder.build(initial);
+        let tape = Unbounded::from_input(input, BLANK);
+        let mut machine = TuringMachine::new(prog, tape);
+        assert!(machine.run());
+        trim_blanks(machine.get_tape(), BLANK).collect()
+    }
+
+    #[test]
+    fn parse_and_write() {
+        let program = parse(SUCCESSOR).expect("Valid program.");
+        assert_eq!(
+            program,
+            vec![
+                Instruction::JumpIfBlank(4),
+                Instruction::Right,
+                Instruction::Jump(1),
+                Instruction::Mark,
+                Instruction::Halt,
+            ]
+        );
+        assert_eq!(parse(&emit(&program)), Ok(program));
+    }
+
+    #[test]
+    fn successor() {
+        let program = parse(SUCCESSOR).expect("Valid program.");
+        let marks = run(compile(&program), INITIAL, vec![MARK; 3]);
+        assert_eq!(marks, vec![MARK; 4]);
+    }
+
+    #[test]
+    fn decompile_busy_beaver() {
+        let builder = busy_beaver::parse("1RB1LB_1LA1RZ").expect("Valid machine.");
+        let program = decompile(&builder, busy_beaver::INITIAL, false).expect("Two symbols.");
+        let marks = run(compile(&program), INITIAL, Vec::new());
+        assert_eq!(marks, vec![MARK; 4]);
+    }
+
+    #[test]
+    fn read_and_write_programs() {
+        let prog = read(SUCCESSOR).expect("Valid program.");
+        let table = prog.into_single_track().expect("Single track.");
+        let tape = Unbounded::from_input(vec!["1".into(); 2], "0".into());
+        let mut machine = TuringMachine::new(table, tape);
+        assert!(machine.run());
+        let marks = trim_blanks(machine.get_tape(), "0".into()).collect::<Vec<SmolStr>>();
+        assert_eq!(marks, vec!["1"; 3]);
+
+        // A morphett.info machine over `_` and `x` comes out over blanks and marks.
+        let prog = morphett::read("0 _ x r 1\n1 _ x * halt\n").expect("Valid machine.");
+        let program = parse(&write(&prog, true).expect("Two symbols.")).expect("Valid program.");
+        let marks = run(compile(&program), INITIAL, Vec::new());
+        assert_eq!(marks, vec![MARK; 2]);
+        let prog = morphett::read("0 _ x r 0\n0 x y * halt\n").expect("Valid machine.");
+        assert!(write(&prog, true).is_err());
+    }
+
+    #[test]
+    fn rejecting_needs_halt_anyway() {
+        // Neither state has a transition for `x`, where the program would halt.
+        let prog = morphett::read("0 _ x r 1\n1 _ x * halt\n").expect("Valid machine.");
+        let err = write(&prog, false).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("\"0\" has no transition reading \"x\""),
+            "{}",
+            err
+        );
+        let prog = morphett::read("0 * * r 1\n1 _ x * halt-reject\n1 x x * halt\n");
+        let err = write(&prog.expect("Valid machine."), false).unwrap_err();
+        assert!(
+            err.to_string().contains("\"1\" reading \"_\" rejects"),
+            "{}",
+            err
+        );
+
+        let mut builder = ProgramBuilder::new();
+        builder.add_transition((INITIAL, BLANK), (Goto::Halt(false), BLANK, None));
+        assert!(decompile(&builder, INITIAL, false).is_err());
+        builder.add_transition((INITIAL, MARK), (Goto::Halt(true), MARK, None));
+        assert!(decompile(&builder, INITIAL, false).is_err());
+        assert_eq!(
+            decompile(&builder, INITIAL, true),
+            Ok(vec![
+                Instruction::JumpIfMarked(3),
+                Instruction::Halt,
+                Instruction::Halt,
+            ])
+        );
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let err = parse("1 MARK\n\n3 HALT\n").unwrap_err();
+        assert!(err.to_string().starts_with("Line 3:"), "{}", err);
+        let err = parse("1 MARK\n2 JUMP 7\n").unwrap_err();
+        assert!(err.to_string().starts_with("Line 2:"), "{}", err);
+        let err = parse("1 PRINT\n").unwrap_err();
+        assert!(err.to_string().starts_with("Line 1:"), "{}", err);
+    }
+}
@@ -1,22 +1,46 @@
-use std::{collections::HashMap, fmt::Debug, hash::Hash};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    hash::Hash,
+};
 
-pub trait TransitionFn<State, Alphabet>:
-    Fn(&State, &Alphabet) -> Response<State, Alphabet>
-{
+pub trait TransitionFn<State, Alphabet> {
+    /// The state a machine running this function starts in.
+    fn initial(&self) -> &State;
+
+    fn respond(&self, state: &State, read: &Alphabet) -> Response<State, Alphabet>;
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Movement {
     Left,
     Right,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Goto<State> {
     Run(State),
+    /// Keep running in the given state, after emitting the output tape as one enumerated word.
+    Emit(State),
     Halt(bool),
 }
 
+/// What a pattern transition writes: a given symbol, or the one it read.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Write<Alphabet> {
+    Symbol(Alphabet),
+    Same,
+}
+
+impl Movement {
+    pub fn opposite(&self) -> Self {
+        match self {
+            Movement::Left => Movement::Right,
+            Movement::Right => Movement::Left,
+        }
+    }
+}
+
 pub struct Stimulus<State, Alphabet> {
     pub state: State,
     pub read: Alphabet,
@@ -27,11 +51,55 @@ pub struct Response<State, Alphabet> {
     pub goto: Goto<State>,
     pub write: Alphabet,
     pub mv: Option<Movement>,
+    /// A symbol to append to the output tape, for machines that compute a function.
+    pub output: Option<Alphabet>,
+}
+
+impl<State, Alphabet> Response<State, Alphabet> {
+    /// A response that appends nothing to the output tape.
+    pub fn new(goto: Goto<State>, write: Alphabet, mv: Option<Movement>) -> Self {
+        Self {
+            goto,
+            write,
+            mv,
+            output: None,
+        }
+    }
 }
 
-impl<T, State, Alphabet> TransitionFn<State, Alphabet> for T where
-    T: Fn(&State, &Alphabet) -> Response<State, Alphabet>
+impl<Alphabet: Clone> Write<Alphabet> {
+    pub fn resolve(&self, read: &Alphabet) -> Alphabet {
+        match self {
+            Write::Symbol(symbol) => symbol.clone(),
+            Write::Same => read.clone(),
+        }
+    }
+}
+
+impl<State: Clone, Alphabet: Clone> Response<State, Write<Alphabet>> {
+    /// Fills in the symbols a pattern transition writes, given the one it read.
+    pub fn resolve(&self, read: &Alphabet) -> Response<State, Alphabet> {
+        Response {
+            goto: self.goto.clone(),
+            write: self.write.resolve(read),
+            mv: self.mv.clone(),
+            output: self.output.as_ref().map(|output| output.resolve(read)),
+        }
+    }
+}
+
+/// A closure paired with the state to start in.
+impl<F, State, Alphabet> TransitionFn<State, Alphabet> for (State, F)
+where
+    F: Fn(&State, &Alphabet) -> Response<State, Alphabet>,
 {
+    fn initial(&self) -> &State {
+        &self.0
+    }
+
+    fn respond(&self, state: &State, read: &Alphabet) -> Response<State, Alphabet> {
+        (self.1)(state, read)
+    }
 }
 
 impl<State, Alphabet> From<(State, Alphabet)> for Stimulus<State, Alphabet> {
@@ -44,10 +112,27 @@ impl<State, Alphabet> From<(Goto<State>, Alphabet, Option<Movement>)>
     for Response<State, Alphabet>
 {
     fn from((goto, write, mv): (Goto<State>, Alphabet, Option<Movement>)) -> Self {
-        Self { goto, write, mv }
+        Self::new(goto, write, mv)
+    }
+}
+
+impl<State, Alphabet> From<(Goto<State>, Alphabet, Option<Movement>, Option<Alphabet>)>
+    for Response<State, Alphabet>
+{
+    fn from(
+        (goto, write, mv, output): (Goto<State>, Alphabet, Option<Movement>, Option<Alphabet>),
+    ) -> Self {
+        Self {
+            goto,
+            write,
+            mv,
+            output,
+        }
     }
 }
 
+/// A table of transitions. When several match, a specific symbol beats a class,
+/// which beats the wildcard.
 #[derive(Debug, Clone)]
 pub struct ProgramBuilder<State, Alphabet>
 where
@@ -55,6 +140,21 @@ where
     Alphabet: Hash + Eq,
 {
     table: HashMap<State, HashMap<Alphabet, Response<State, Alphabet>>>,
+    /// Responses to any symbol without a more specific transition.
+    wildcards: HashMap<State, Response<State, Write<Alphabet>>>,
+}
+
+impl<State, Alphabet> Default for ProgramBuilder<State, Alphabet>
+where
+    State: Hash + Eq,
+    Alphabet: Hash + Eq,
+{
+    fn default() -> Self {
+        Self {
+            table: HashMap::new(),
+            wildcards: HashMap::new(),
+        }
+    }
 }
 
 impl<State, Alphabet> ProgramBuilder<State, Alphabet>
@@ -63,26 +163,33 @@ where
     Alphabet: Hash + Eq + Clone,
 {
     pub fn new() -> Self {
-        Self {
-            table: HashMap::new(),
-        }
+        Default::default()
     }
 
-    pub fn build(self) -> impl TransitionFn<State, Alphabet> {
-        let table = self.table;
-        move |state: &State, symbol: &Alphabet| match table
-            .get(state)
-            .and_then(|subtable| subtable.get(symbol))
-        {
-            Some(response) => return response.clone(),
-            None => Response {
-                goto: Goto::Halt(false),
-                write: symbol.clone(),
-                mv: None,
-            },
+    pub fn build(self, initial: State) -> Program<State, Alphabet> {
+        Program {
+            initial,
+            table: self.table,
+            wildcards: self.wildcards,
         }
     }
 
+    /// Every transition added so far, with classes expanded, in no particular order.
+    /// Wildcard transitions aren't included since they match symbols nobody listed.
+    pub fn transitions(
+        &self,
+    ) -> impl Iterator<Item = (&State, &Alphabet, &Response<State, Alphabet>)> {
+        self.table.iter().flat_map(|(state, subtable)| {
+            subtable
+                .iter()
+                .map(move |(read, response)| (state, read, response))
+        })
+    }
+
+    pub fn has_wildcards(&self) -> bool {
+        !self.wildcards.is_empty()
+    }
+
     pub fn add_transition<S, R>(&mut self, stimulus: S, response: R)
     where
         Stimulus<State, Alphabet>: From<S>,
@@ -90,12 +197,36 @@ where
     {
         let stimulus: Stimulus<_, _> = stimulus.into();
         let response = response.into();
+        // Replaces whatever a class transition put here.
         self.table
             .entry(stimulus.state)
             .or_default()
             .insert(stimulus.read, response);
     }
 
+    /// Adds a transition for every symbol in `class`, except those that already have a specific
+    /// transition or belong to a class added earlier.
+    pub fn add_class_transition<C, R>(&mut self, state: State, class: C, response: R)
+    where
+        C: IntoIterator<Item = Alphabet>,
+        Response<State, Write<Alphabet>>: From<R>,
+    {
+        let response = Response::from(response);
+        let subtable = self.table.entry(state).or_default();
+        for read in class {
+            let response = response.resolve(&read);
+            subtable.entry(read).or_insert(response);
+        }
+    }
+
+    /// Adds a transition for any symbol that has no specific or class transition.
+    pub fn add_wildcard_transition<R>(&mut self, state: State, response: R)
+    where
+        Response<State, Write<Alphabet>>: From<R>,
+    {
+        self.wildcards.insert(state, response.into());
+    }
+
     pub fn with_transition<S, R>(mut self, stimulus: S, response: R) -> Self
     where
         Stimulus<State, Alphabet>: From<S>,
@@ -104,6 +235,125 @@ where
         self.add_transition(stimulus, response);
         self
     }
+
+    pub fn with_class_transition<C, R>(mut self, state: State, class: C, response: R) -> Self
+    where
+        C: IntoIterator<Item = Alphabet>,
+        Response<State, Write<Alphabet>>: From<R>,
+    {
+        self.add_class_transition(state, class, response);
+        self
+    }
+
+    pub fn with_wildcard_transition<R>(mut self, state: State, response: R) -> Self
+    where
+        Response<State, Write<Alphabet>>: From<R>,
+    {
+        self.add_wildcard_transition(state, response);
+        self
+    }
+}
+
+/// A built transition table. Unlike a closure, it can list what it's made of.
+#[derive(Debug, Clone)]
+pub struct Program<State, Alphabet>
+where
+    State: Hash + Eq,
+    Alphabet: Hash + Eq,
+{
+    initial: State,
+    table: HashMap<State, HashMap<Alphabet, Response<State, Alphabet>>>,
+    wildcards: HashMap<State, Response<State, Write<Alphabet>>>,
+}
+
+impl<State, Alphabet> Program<State, Alphabet>
+where
+    State: Hash + Eq + Clone,
+    Alphabet: Hash + Eq + Clone,
+{
+    /// The transition for exactly this state and symbol.
+    /// Wildcard transitions aren't found here, see `wildcard`.
+    pub fn lookup(&self, state: &State, read: &Alphabet) -> Option<&Response<State, Alphabet>> {
+        self.table
+            .get(state)
+            .and_then(|subtable| subtable.get(read))
+    }
+
+    /// The transition taken from `state` on symbols without one of their own.
+    pub fn wildcard(&self, state: &State) -> Option<&Response<State, Write<Alphabet>>> {
+        self.wildcards.get(state)
+    }
+
+    /// Every transition on a specific symbol, with classes expanded, in no particular order.
+    pub fn transitions(
+        &self,
+    ) -> impl Iterator<Item = (&State, &Alphabet, &Response<State, Alphabet>)> {
+        self.table.iter().flat_map(|(state, subtable)| {
+            subtable
+                .iter()
+                .map(move |(read, response)| (state, read, response))
+        })
+    }
+
+    /// The initial state and every state a transition leaves or enters.
+    pub fn states(&self) -> HashSet<&State> {
+        let mut states = HashSet::new();
+        states.insert(&self.initial);
+        let responses = self
+            .transitions()
+            .map(|(state, _, response)| (state, &response.goto))
+            .chain(
+                self.wildcards
+                    .iter()
+                    .map(|(state, response)| (state, &response.goto)),
+            );
+        for (state, goto) in responses {
+            states.insert(state);
+            if let Goto::Run(goto) | Goto::Emit(goto) = goto {
+                states.insert(goto);
+            }
+        }
+        states
+    }
+
+    /// Every symbol a transition reads, writes or outputs.
+    pub fn symbols(&self) -> HashSet<&Alphabet> {
+        let mut symbols = HashSet::new();
+        for (_, read, response) in self.transitions() {
+            symbols.insert(read);
+            symbols.insert(&response.write);
+            symbols.extend(&response.output);
+        }
+        for response in self.wildcards.values() {
+            let written = Some(&response.write).into_iter().chain(&response.output);
+            for write in written {
+                if let Write::Symbol(symbol) = write {
+                    symbols.insert(symbol);
+                }
+            }
+        }
+        symbols
+    }
+}
+
+impl<State, Alphabet> TransitionFn<State, Alphabet> for Program<State, Alphabet>
+where
+    State: Hash + Eq + Clone,
+    Alphabet: Hash + Eq + Clone,
+{
+    fn initial(&self) -> &State {
+        &self.initial
+    }
+
+    fn respond(&self, state: &State, read: &Alphabet) -> Response<State, Alphabet> {
+        if let Some(response) = self.lookup(state, read) {
+            return response.clone();
+        }
+        match self.wildcards.get(state) {
+            Some(response) => response.resolve(read),
+            None => Response::new(Goto::Halt(false), read.clone(), None),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -112,14 +362,10 @@ mod tests {
 
     #[test]
     fn empty() {
-        let prog = ProgramBuilder::new().build();
+        let prog = ProgramBuilder::new().build(0);
         assert_eq!(
-            prog(&0, &0),
-            Response {
-                goto: Goto::Halt(false),
-                write: 0,
-                mv: None
-            }
+            prog.respond(&0, &0),
+            Response::new(Goto::Halt(false), 0, None)
         );
     }
 
@@ -127,14 +373,40 @@ mod tests {
     fn nonempty() {
         let prog = ProgramBuilder::new()
             .with_transition((0, 0), (Goto::Run(1), 1, Some(Movement::Right)))
-            .build();
+            .build(0);
         assert_eq!(
-            prog(&0, &0),
-            Response {
-                goto: Goto::Run(1),
-                write: 1,
-                mv: Some(Movement::Right)
-            }
+            prog.respond(&0, &0),
+            Response::new(Goto::Run(1), 1, Some(Movement::Right))
         );
     }
+
+    #[test]
+    fn precedence() {
+        let prog = ProgramBuilder::new()
+            .with_wildcard_transition(0, (Goto::Run(3), Write::Same, None))
+            .with_transition((0, 1), (Goto::Run(1), 1, None))
+            .with_class_transition(0, vec![1, 2], (Goto::Run(2), Write::Symbol(9), None))
+            .build(0);
+        assert_eq!(prog.respond(&0, &1).goto, Goto::Run(1));
+        assert_eq!(prog.respond(&0, &2).goto, Goto::Run(2));
+        assert_eq!(prog.respond(&0, &2).write, 9);
+        assert_eq!(prog.respond(&0, &5).goto, Goto::Run(3));
+        assert_eq!(prog.respond(&0, &5).write, 5);
+        assert_eq!(prog.respond(&1, &5).goto, Goto::Halt(false));
+    }
+
+    #[test]
+    fn introspection() {
+        let prog = ProgramBuilder::new()
+            .with_transition((0, 0), (Goto::Run(1), 1, Some(Movement::Right)))
+            .with_transition((1, 1), (Goto::Halt(true), 2, None))
+            .with_wildcard_transition(1, (Goto::Run(2), Write::Symbol(3), None))
+            .build(0);
+        assert_eq!(*prog.initial(), 0);
+        assert_eq!(prog.lookup(&0, &0).map(|r| r.write), Some(1));
+        assert_eq!(prog.lookup(&1, &0), None);
+        assert_eq!(prog.transitions().count(), 2);
+        assert_eq!(prog.states(), [0, 1, 2].iter().collect());
+        assert_eq!(prog.symbols(), [0, 1, 2, 3].iter().collect());
+    }
 }
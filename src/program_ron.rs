@@ -1,62 +1,1881 @@
-use serde::Deserialize;
+use serde::{
+    de::DeserializeOwned,
+    de::{
+        value::{MapAccessDeserializer, SeqAccessDeserializer},
+        DeserializeSeed, Error as _, MapAccess, SeqAccess, Visitor,
+    },
+    Deserialize, Deserializer,
+};
 use smol_str::SmolStr;
 
-use std::{collections::HashMap, io::Read};
+use std::{
+    borrow::Borrow,
+    cell::RefCell,
+    collections::{HashMap, HashSet, VecDeque},
+    error::Error,
+    fmt,
+    fs::File,
+    hash::Hash,
+    io::Read,
+    marker::PhantomData,
+    mem,
+    path::{Path, PathBuf},
+};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    automaton::{Dfa, DfaBuilder, Nfa, NfaBuilder, Pda, PdaBuilder},
+    diagnostic::Diagnostic,
+    multitrack::{MultiTrackBuilder, MultiTrackProgram, TrackPattern},
+    program,
+    program::{Goto, ProgramBuilder, Write},
+};
+
+/// Which kind of machine a program file describes. Each kind has its own shape of transitions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum Kind {
+    #[default]
+    Turing,
+    Dfa,
+    Nfa,
+    Pda,
+}
+
+/// Just enough of a program to know how to read the rest of it.
+#[derive(Debug, Deserialize)]
+#[serde(rename = "Program")]
+struct Header {
+    #[serde(default)]
+    kind: Kind,
+}
+
+/// In finite and pushdown automata the empty string stands for "read nothing", an epsilon move.
+pub const EPSILON: &str = "";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename = "Program")]
+struct DfaProgram {
+    initial: String,
+    accept: Vec<String>,
+    #[serde(deserialize_with = "table::<_, DfaTransition, _, _>")]
+    transitions: Vec<((String, String), String)>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DfaTransition {
+    state: String,
+    read: String,
+    goto: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename = "Program")]
+struct NfaProgram {
+    initial: String,
+    accept: Vec<String>,
+    #[serde(deserialize_with = "table::<_, NfaTransition, _, _>")]
+    transitions: Vec<((String, String), Vec<String>)>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NfaTransition {
+    state: String,
+    read: String,
+    goto: Vec<String>,
+}
+
+type PdaAction = (String, Vec<String>);
+
+/// Transitions are keyed by `(state, input, pop)` and list `(goto, push)` moves,
+/// with the symbols to push written top first.
+#[derive(Debug, Deserialize)]
+#[serde(rename = "Program")]
+struct PdaProgram {
+    initial: String,
+    accept: Vec<String>,
+    #[serde(deserialize_with = "table::<_, PdaTransition, _, _>")]
+    transitions: Vec<((String, String, String), Vec<PdaAction>)>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PdaTransition {
+    state: String,
+    read: String,
+    pop: String,
+    moves: Vec<PdaAction>,
+}
+
+impl From<DfaTransition> for ((String, String), String) {
+    fn from(t: DfaTransition) -> Self {
+        ((t.state, t.read), t.goto)
+    }
+}
+
+impl From<NfaTransition> for ((String, String), Vec<String>) {
+    fn from(t: NfaTransition) -> Self {
+        ((t.state, t.read), t.goto)
+    }
+}
+
+impl From<PdaTransition> for ((String, String, String), Vec<PdaAction>) {
+    fn from(t: PdaTransition) -> Self {
+        ((t.state, t.read, t.pop), t.moves)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+enum Movement {
+    Stay,
+    Left,
+    Right,
+}
+
+/// Either a single symbol, or one symbol per track written as a tuple such as `("a", "*")`,
+/// where `"*"` is the declared wildcard.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq, Hash)]
+#[serde(untagged)]
+enum Symbol {
+    Single(String),
+    Tracks(Vec<String>),
+}
+
+/// The right hand side of a transition, `(goto, write, move)`.
+/// A fourth element, if present, is appended to the output tape.
+#[derive(Debug, Clone)]
+struct Action {
+    goto: String,
+    write: Symbol,
+    mv: Movement,
+    output: Option<Symbol>,
+}
+
+impl<'de> Deserialize<'de> for Action {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ActionVisitor;
+
+        impl<'de> Visitor<'de> for ActionVisitor {
+            type Value = Action;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(
+                    f,
+                    "a (goto, write, move) or (goto, write, move, output) tuple"
+                )
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Action, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let goto = seq
+                    .next_element()?
+                    .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+                let write = seq
+                    .next_element()?
+                    .ok_or_else(|| A::Error::invalid_length(1, &self))?;
+                let mv = seq
+                    .next_element()?
+                    .ok_or_else(|| A::Error::invalid_length(2, &self))?;
+                let output = seq.next_element()?;
+                Ok(Action {
+                    goto,
+                    write,
+                    mv,
+                    output,
+                })
+            }
+        }
+
+        deserializer.deserialize_tuple(4, ActionVisitor)
+    }
+}
+
+/// A Turing machine as written in a file, before classes, wildcards and templates become
+/// plain transitions. Every format reads into and writes from this, and running it goes through
+/// `into_builder` to a `program::Program`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename = "Program")]
+pub struct ProgramFile {
+    initial: String,
+    /// The symbol the tape is filled with, the empty string unless declared.
+    #[serde(default)]
+    blank: String,
+    /// States of an enumerator that print the output tape as a word when entered.
+    #[serde(default)]
+    print: Vec<String>,
+    /// Named sets of symbols. A transition reading a class's name applies to each of its members,
+    /// unless one has a transition of its own.
+    #[serde(default)]
+    classes: HashMap<String, Vec<String>>,
+    /// The symbol meaning "any symbol" when reading and "unchanged" when writing.
+    /// In a multi-track program it stands for a single track.
+    /// Unless one is declared, every symbol means itself.
+    #[serde(default, deserialize_with = "declared")]
+    wildcard: Option<String>,
+    /// Other machines, by namespace. Going to a namespace calls the machine, whose states are
+    /// renamed `namespace/state`.
+    #[serde(default)]
+    include: HashMap<String, Include>,
+    /// Parameters of state templates, each ranging over a class.
+    /// A transition mentioning `{name}` in its key is expanded once per value.
+    #[serde(default)]
+    parameters: HashMap<String, String>,
+    /// Halting states, `["accept"]` and `["reject"]` unless declared.
+    #[serde(default = "default_accept")]
+    accept: Vec<String>,
+    #[serde(default = "default_reject")]
+    reject: Vec<String>,
+    /// The formal 7-tuple's sets, checked against the transitions when declared.
+    #[serde(default, deserialize_with = "declared")]
+    states: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "declared")]
+    input_alphabet: Option<Vec<String>>,
+    /// Always includes the blank, whether or not it's listed.
+    #[serde(default, deserialize_with = "declared")]
+    tape_alphabet: Option<Vec<String>>,
+    /// Every entry in the order it was written; `parse` rejects duplicate keys.
+    /// Written either as a map keyed by `(state, read)` or as a list of `Transition` objects.
+    #[serde(deserialize_with = "entries")]
+    transitions: Vec<Entry>,
+}
+
+/// A machine from another file, and the states its halting states lead to in the caller.
+/// Without an exit, halting halts the whole machine.
+#[derive(Debug, Clone, Deserialize)]
+struct Include {
+    file: PathBuf,
+    #[serde(default, deserialize_with = "declared")]
+    accept: Option<String>,
+    #[serde(default, deserialize_with = "declared")]
+    reject: Option<String>,
+}
+
+/// A transition keyed by `(state, read)`.
+type Entry = ((String, Symbol), Action);
+
+/// A transition written as an object, for formats like JSON that have no tuple keys.
+#[derive(Debug, Deserialize)]
+struct Transition {
+    state: String,
+    read: Symbol,
+    goto: String,
+    write: Symbol,
+    #[serde(rename = "move")]
+    mv: Movement,
+    #[serde(default, deserialize_with = "declared")]
+    output: Option<Symbol>,
+}
+
+impl Transition {
+    fn into_entry(self) -> Entry {
+        let action = Action {
+            goto: self.goto,
+            write: self.write,
+            mv: self.mv,
+            output: self.output,
+        };
+        ((self.state, self.read), action)
+    }
+}
+
+/// The serde formats a `ProgramFile` can be written in. They share one schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    Ron,
+    Json,
+    Yaml,
+    Toml,
+}
+
+/// A single-track transition spelled out, for converting to and from other formats.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub state: String,
+    pub read: String,
+    pub goto: String,
+    pub write: String,
+    pub mv: Option<program::Movement>,
+}
+
+impl ProgramFile {
+    /// A program without transitions, halting in `"accept"` and `"reject"`.
+    pub fn new(initial: impl Into<String>, blank: impl Into<String>) -> Self {
+        Self {
+            initial: initial.into(),
+            blank: blank.into(),
+            print: Vec::new(),
+            classes: HashMap::new(),
+            wildcard: None,
+            include: HashMap::new(),
+            parameters: HashMap::new(),
+            accept: default_accept(),
+            reject: default_reject(),
+            states: None,
+            input_alphabet: None,
+            tape_alphabet: None,
+            transitions: Vec::new(),
+        }
+    }
+
+    pub fn initial(&self) -> &str {
+        &self.initial
+    }
+
+    pub fn accept_states(&self) -> &[String] {
+        &self.accept
+    }
+
+    pub fn reject_states(&self) -> &[String] {
+        &self.reject
+    }
+
+    pub fn set_halting(&mut self, accept: Vec<String>, reject: Vec<String>) {
+        self.accept = accept;
+        self.reject = reject;
+    }
+
+    /// Makes `wildcard` match any symbol when read and leave the cell unchanged when written.
+    pub fn set_wildcard(&mut self, wildcard: impl Into<String>) {
+        self.wildcard = Some(wildcard.into());
+    }
+
+    /// Fails if the program already has a transition for the rule's state and symbol.
+    pub fn add_rule(&mut self, rule: Rule) -> Result<(), ron::de::Error> {
+        let key = (rule.state, Symbol::Single(rule.read));
+        let action = Action {
+            goto: rule.goto,
+            write: Symbol::Single(rule.write),
+            mv: match rule.mv {
+                None => Movement::Stay,
+                Some(program::Movement::Left) => Movement::Left,
+                Some(program::Movement::Right) => Movement::Right,
+            },
+            output: None,
+        };
+        if let Some((_, first)) = self.transitions.iter().find(|(k, _)| *k == key) {
+            let key = quote_key(&key.0, &key.1);
+            return Err(ron::de::Error::Message(format!(
+                "Duplicate transition:\n    {}: {}\n    {}: {}",
+                key,
+                first.quote(),
+                key,
+                action.quote()
+            )));
+        }
+        self.transitions.push((key, action));
+        Ok(())
+    }
+
+    /// Every transition as a single-track rule, with classes and wildcards expanded over the
+    /// program's symbols. Fails for what a plain table can't express: tracks, output and printing.
+    pub fn rules(&self) -> Result<Vec<Rule>, ron::de::Error> {
+        let unsupported = |what: &str| {
+            ron::de::Error::Message(format!("This program {}, which only RON supports.", what))
+        };
+        if self.is_enumerator() {
+            return Err(unsupported("prints words"));
+        }
+        if self.is_transducer() {
+            return Err(unsupported("writes to an output tape"));
+        }
+        let accept = self
+            .accept
+            .first()
+            .cloned()
+            .unwrap_or_else(|| "accept".into());
+        let reject = self
+            .reject
+            .first()
+            .cloned()
+            .unwrap_or_else(|| "reject".into());
+        let name = |goto: &Goto<SmolStr>| match goto {
+            Goto::Run(state) | Goto::Emit(state) => state.to_string(),
+            Goto::Halt(true) => accept.clone(),
+            Goto::Halt(false) => reject.clone(),
+        };
+        let rule =
+            |state: &SmolStr, read: &SmolStr, response: &program::Response<_, SmolStr>| Rule {
+                state: state.to_string(),
+                read: read.to_string(),
+                goto: name(&response.goto),
+                write: response.write.to_string(),
+                mv: response.mv.clone(),
+            };
+
+        let table = self.clone().into_single_track()?;
+        let mut rules = table
+            .transitions()
+            .map(|(state, read, response)| rule(state, read, response))
+            .collect::<Vec<_>>();
+        let alphabet = self.alphabet();
+        for state in table.states() {
+            if let Some(response) = table.wildcard(state) {
+                for read in &alphabet {
+                    let read = SmolStr::from(read.as_str());
+                    if table.lookup(state, &read).is_none() {
+                        rules.push(rule(state, &read, &response.resolve(&read)));
+                    }
+                }
+            }
+        }
+
+        // Keep states in the order the program first mentions them.
+        let mut order = HashMap::new();
+        let mentioned = Some(&self.initial)
+            .into_iter()
+            .chain(self.transitions.iter().map(|((state, _), _)| state));
+        for state in mentioned {
+            let next = order.len();
+            order.entry(state.as_str()).or_insert(next);
+        }
+        rules.sort_by(|a, b| {
+            let rank = |rule: &Rule| {
+                order
+                    .get(rule.state.as_str())
+                    .copied()
+                    .unwrap_or(usize::MAX)
+            };
+            (rank(a), &a.state, &a.read).cmp(&(rank(b), &b.state, &b.read))
+        });
+        Ok(rules)
+    }
+
+    /// The tape alphabet if declared, otherwise every symbol the program mentions.
+    /// Either way it includes the blank.
+    fn alphabet(&self) -> Vec<String> {
+        let mut symbols = match &self.tape_alphabet {
+            Some(alphabet) => alphabet.clone(),
+            None => {
+                let mut symbols = Vec::new();
+                for ((_, read), action) in &self.transitions {
+                    if !self.is_class(read) {
+                        symbols.extend(read.symbols().into_iter().map(String::from));
+                    }
+                    symbols.extend(action.write.symbols().into_iter().map(String::from));
+                }
+                symbols.extend(self.classes.values().flatten().cloned());
+                symbols.retain(|s| !self.is_wildcard(s));
+                symbols
+            }
+        };
+        symbols.extend(self.input_alphabet.iter().flatten().cloned());
+        symbols.push(self.blank.clone());
+        symbols.sort();
+        symbols.dedup();
+        symbols
+    }
+
+    /// Writes the program back out as RON. Templates and includes have already been expanded,
+    /// so they come out flattened.
+    pub fn to_ron(&self) -> String {
+        let list = |items: &[String]| {
+            let items = items.iter().map(|s| ron_string(s)).collect::<Vec<_>>();
+            format!("[{}]", items.join(", "))
+        };
+        let mut out = String::from("Program(\n");
+        out.push_str(&format!("    initial: {},\n", ron_string(&self.initial)));
+        if !self.blank.is_empty() {
+            out.push_str(&format!("    blank: {},\n", ron_string(&self.blank)));
+        }
+        if self.accept != default_accept() {
+            out.push_str(&format!("    accept: {},\n", list(&self.accept)));
+        }
+        if self.reject != default_reject() {
+            out.push_str(&format!("    reject: {},\n", list(&self.reject)));
+        }
+        if !self.print.is_empty() {
+            out.push_str(&format!("    print: {},\n", list(&self.print)));
+        }
+        if let Some(wildcard) = &self.wildcard {
+            out.push_str(&format!("    wildcard: {},\n", ron_string(wildcard)));
+        }
+        let declared = [
+            ("states", &self.states),
+            ("input_alphabet", &self.input_alphabet),
+            ("tape_alphabet", &self.tape_alphabet),
+        ];
+        for (field, items) in declared.iter() {
+            if let Some(items) = items {
+                out.push_str(&format!("    {}: {},\n", field, list(items)));
+            }
+        }
+        if !self.classes.is_empty() {
+            let mut classes = self.classes.iter().collect::<Vec<_>>();
+            classes.sort();
+            let classes = classes
+                .into_iter()
+                .map(|(name, class)| format!("{}: {}", ron_string(name), list(class)))
+                .collect::<Vec<_>>();
+            out.push_str(&format!("    classes: {{{}}},\n", classes.join(", ")));
+        }
+        out.push_str("    transitions: {\n");
+        for ((state, read), action) in &self.transitions {
+            out.push_str(&format!(
+                "        ({}, {}): ({}, {}, {:?}",
+                ron_string(state),
+                read.to_ron(),
+                ron_string(&action.goto),
+                action.write.to_ron(),
+                action.mv
+            ));
+            if let Some(output) = &action.output {
+                out.push_str(&format!(", {}", output.to_ron()));
+            }
+            out.push_str("),\n");
+        }
+        out.push_str("    },\n)\n");
+        out
+    }
+
+    /// Writes the program out as YAML in the same schema, with transitions as a list of
+    /// objects. Strings are double-quoted, escaped as in JSON.
+    pub fn to_yaml(&self) -> String {
+        let list = |items: &[String]| {
+            let items = items.iter().map(|s| yaml_string(s)).collect::<Vec<_>>();
+            format!("[{}]", items.join(", "))
+        };
+        let mut out = format!("initial: {}\n", yaml_string(&self.initial));
+        if !self.blank.is_empty() {
+            out.push_str(&format!("blank: {}\n", yaml_string(&self.blank)));
+        }
+        if self.accept != default_accept() {
+            out.push_str(&format!("accept: {}\n", list(&self.accept)));
+        }
+        if self.reject != default_reject() {
+            out.push_str(&format!("reject: {}\n", list(&self.reject)));
+        }
+        if !self.print.is_empty() {
+            out.push_str(&format!("print: {}\n", list(&self.print)));
+        }
+        if let Some(wildcard) = &self.wildcard {
+            out.push_str(&format!("wildcard: {}\n", yaml_string(wildcard)));
+        }
+        let declared = [
+            ("states", &self.states),
+            ("input_alphabet", &self.input_alphabet),
+            ("tape_alphabet", &self.tape_alphabet),
+        ];
+        for (field, items) in declared.iter() {
+            if let Some(items) = items {
+                out.push_str(&format!("{}: {}\n", field, list(items)));
+            }
+        }
+        if !self.classes.is_empty() {
+            let mut classes = self.classes.iter().collect::<Vec<_>>();
+            classes.sort();
+            out.push_str("classes:\n");
+            for (name, class) in classes {
+                out.push_str(&format!("  {}: {}\n", yaml_string(name), list(class)));
+            }
+        }
+        out.push_str("transitions:\n");
+        for ((state, read), action) in &self.transitions {
+            out.push_str(&format!(
+                "  - {{state: {}, read: {}, goto: {}, write: {}, move: {:?}",
+                yaml_string(state),
+                read.to_yaml(),
+                yaml_string(&action.goto),
+                action.write.to_yaml(),
+                action.mv
+            ));
+            if let Some(output) = &action.output {
+                out.push_str(&format!(", output: {}", output.to_yaml()));
+            }
+            out.push_str("}\n");
+        }
+        out
+    }
+
+    /// Finds the first input symbol outside the declared input alphabet, if there is one.
+    pub fn check_input<S>(&self, input: &[S]) -> Result<(), UnexpectedInput>
+    where
+        S: Borrow<str>,
+    {
+        let mut check = self.input_checker();
+        for symbol in input {
+            check(SmolStr::from(symbol.borrow()))?;
+        }
+        Ok(())
+    }
+
+    /// Checks input one symbol at a time, in order, so it can be checked as it's read.
+    /// Without a declared input alphabet every symbol passes.
+    pub fn input_checker(&self) -> impl FnMut(SmolStr) -> Result<SmolStr, UnexpectedInput> {
+        let alphabet = self.input_alphabet.clone();
+        let mut position = 0;
+        move |symbol| {
+            let allowed = alphabet
+                .as_ref()
+                .is_none_or(|alphabet| alphabet.iter().any(|s| s == symbol));
+            if !allowed {
+                return Err(UnexpectedInput { position, symbol });
+            }
+            position += 1;
+            Ok(symbol)
+        }
+    }
+
+    /// Included files are looked up relative to the working directory.
+    pub fn parse<R>(rdr: R) -> Result<Self, ron::de::Error>
+    where
+        R: Read,
+    {
+        Self::parse_as(rdr, Syntax::Ron)
+    }
+
+    pub fn parse_as<R>(rdr: R, syntax: Syntax) -> Result<Self, ron::de::Error>
+    where
+        R: Read,
+    {
+        Self::parse_in(rdr, syntax, Path::new("."), &mut Vec::new())
+    }
+
+    /// Included files are looked up relative to the program's own file.
+    pub fn load(path: &Path) -> Result<Self, ron::de::Error> {
+        Self::load_as(path, Syntax::from_path(path))
+    }
+
+    pub fn load_as(path: &Path, syntax: Syntax) -> Result<Self, ron::de::Error> {
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        Self::parse_in(
+            File::open(path)?,
+            syntax,
+            dir,
+            &mut vec![path.canonicalize()?],
+        )
+    }
+
+    /// `including` holds the files being loaded, to catch a file that includes itself.
+    fn parse_in<R>(
+        mut rdr: R,
+        syntax: Syntax,
+        dir: &Path,
+        including: &mut Vec<PathBuf>,
+    ) -> Result<Self, ron::de::Error>
+    where
+        R: Read,
+    {
+        let mut code = String::new();
+        rdr.read_to_string(&mut code)?;
+        let mut prog: Self = syntax.deserialize(&code)?;
+        prog.expand_templates()?;
+        prog.check_duplicates()?;
+        prog.resolve_includes(dir, including)?;
+        Ok(prog)
+    }
+
+    /// Loads every included machine and merges its transitions into this program.
+    fn resolve_includes(
+        &mut self,
+        dir: &Path,
+        including: &mut Vec<PathBuf>,
+    ) -> Result<(), ron::de::Error> {
+        let mut includes = mem::take(&mut self.include).into_iter().collect::<Vec<_>>();
+        includes.sort_by(|(a, _), (b, _)| a.cmp(b));
+        for (namespace, include) in includes {
+            let path = dir.join(&include.file);
+            let canonical = path.canonicalize()?;
+            if including.contains(&canonical) {
+                return Err(ron::de::Error::Message(format!(
+                    "{} includes itself.",
+                    path.display()
+                )));
+            }
+            including.push(canonical);
+            let sub_dir = path.parent().unwrap_or(dir);
+            let syntax = Syntax::from_path(&path);
+            let sub = Self::parse_in(File::open(&path)?, syntax, sub_dir, including)?;
+            including.pop();
+            self.merge(&namespace, include, sub)?;
+        }
+        Ok(())
+    }
+
+    /// Adds `sub`'s transitions under `namespace`, sending its halting states to the exits.
+    fn merge(
+        &mut self,
+        namespace: &str,
+        include: Include,
+        mut sub: Self,
+    ) -> Result<(), ron::de::Error> {
+        let error = |message: String| ron::de::Error::Message(message);
+        if sub.blank != self.blank {
+            return Err(error(format!(
+                "{:?} uses the blank {:?}, but the including program uses {:?}.",
+                namespace, sub.blank, self.blank
+            )));
+        }
+        if sub.wildcard != self.wildcard {
+            let describe = |wildcard: &Option<String>| match wildcard {
+                Some(wildcard) => format!("the wildcard {:?}", wildcard),
+                None => "no wildcard".to_owned(),
+            };
+            return Err(error(format!(
+                "{:?} declares {}, but the including program declares {}.",
+                namespace,
+                describe(&sub.wildcard),
+                describe(&self.wildcard)
+            )));
+        }
+        if self
+            .transitions
+            .iter()
+            .any(|((state, _), _)| state == namespace)
+        {
+            return Err(error(format!(
+                "The state {:?} has the same name as an included machine.",
+                namespace
+            )));
+        }
+        let exit = |declared: Option<String>, defaults: &[String]| {
+            declared
+                .or_else(|| defaults.first().cloned())
+                .ok_or_else(|| {
+                    error(format!(
+                    "{:?} needs an exit, since this program has no halting state to default to.",
+                    namespace
+                ))
+                })
+        };
+        let accept = exit(include.accept, &self.accept)?;
+        let reject = exit(include.reject, &self.reject)?;
+        let prefix = |state: &str| format!("{}/{}", namespace, state);
+
+        // Calls go to the included machine's initial state.
+        let entry = prefix(&sub.initial);
+        if self.initial == namespace {
+            self.initial = entry.clone();
+        }
+        for (_, action) in &mut self.transitions {
+            if action.goto == namespace {
+                action.goto = entry.clone();
+            }
+        }
+
+        for ((state, read), mut action) in mem::take(&mut sub.transitions) {
+            let read = match read {
+                Symbol::Single(class) if sub.classes.contains_key(&class) => {
+                    Symbol::Single(prefix(&class))
+                }
+                read => read,
+            };
+            action.goto = if sub.accept.contains(&action.goto) {
+                accept.clone()
+            } else if sub.reject.contains(&action.goto) {
+                reject.clone()
+            } else {
+                prefix(&action.goto)
+            };
+            if let Some(states) = &mut self.states {
+                states.push(prefix(&state));
+                // The exits may be this program's own halting states, which aren't declared.
+                if !self.accept.contains(&action.goto) && !self.reject.contains(&action.goto) {
+                    states.push(action.goto.clone());
+                }
+            }
+            self.transitions.push(((prefix(&state), read), action));
+        }
+        if let Some(states) = &mut self.states {
+            states.push(entry);
+            states.extend(sub.print.iter().map(|state| prefix(state)));
+            states.sort();
+            states.dedup();
+        }
+        for (name, class) in sub.classes {
+            self.classes.insert(prefix(&name), class);
+        }
+        for state in &sub.print {
+            self.print.push(prefix(state));
+        }
+        Ok(())
+    }
+
+    /// Replaces every transition that mentions a parameter, like `"search {b} left"`, with one
+    /// concrete transition per value of the parameters in its key.
+    fn expand_templates(&mut self) -> Result<(), ron::de::Error> {
+        let mut parameters = Vec::new();
+        for (name, class) in &self.parameters {
+            let values = self.classes.get(class).ok_or_else(|| {
+                ron::de::Error::Message(format!(
+                    "Parameter {:?} ranges over {:?}, which is not a class.",
+                    name, class
+                ))
+            })?;
+            parameters.push((format!("{{{}}}", name), values));
+        }
+        // Expand in a fixed order so expanded transitions don't depend on hashing.
+        parameters.sort();
+
+        let mut transitions = Vec::new();
+        for ((state, read), action) in &self.transitions {
+            let mentions = |s: &str, placeholder: &str| s.contains(placeholder);
+            let in_key =
+                |p: &str| mentions(state, p) || read.symbols().iter().any(|s| mentions(s, p));
+            let in_response = |p: &str| {
+                mentions(&action.goto, p)
+                    || action.write.symbols().iter().any(|s| mentions(s, p))
+                    || action
+                        .output
+                        .iter()
+                        .flat_map(Symbol::symbols)
+                        .any(|s| mentions(s, p))
+            };
+
+            let mut bindings: Vec<Vec<(&str, &str)>> = vec![vec![]];
+            for (placeholder, values) in &parameters {
+                if in_key(placeholder) {
+                    bindings = bindings
+                        .into_iter()
+                        .flat_map(|binding| {
+                            values.iter().map(move |value| {
+                                let mut binding = binding.clone();
+                                binding.push((placeholder.as_str(), value.as_str()));
+                                binding
+                            })
+                        })
+                        .collect();
+                } else if in_response(placeholder) {
+                    return Err(ron::de::Error::Message(format!(
+                        "{} uses {} in its response, but not in its key.",
+                        quote_key(state, read),
+                        placeholder
+                    )));
+                }
+            }
+
+            for binding in bindings {
+                let key = (substitute(state, &binding), read.substitute(&binding));
+                transitions.push((key, action.substitute(&binding)));
+            }
+        }
+        self.transitions = transitions;
+        Ok(())
+    }
+
+    /// Fails if templates expanded to the same key twice, naming both entries.
+    /// Keys written twice are already caught while deserializing, where the syntax knows the
+    /// position.
+    fn check_duplicates(&self) -> Result<(), ron::de::Error> {
+        let mut seen = HashMap::new();
+        let mut duplicates = Vec::new();
+        for (i, (key, _)) in self.transitions.iter().enumerate() {
+            if let Some(&first) = seen.get(key) {
+                duplicates.push(format!(
+                    "Duplicate transition:\n    {}\n    {}",
+                    describe_entry(&self.transitions[first]),
+                    describe_entry(&self.transitions[i])
+                ));
+            } else {
+                seen.insert(key, i);
+            }
+        }
+        if duplicates.is_empty() {
+            Ok(())
+        } else {
+            Err(ron::de::Error::Message(duplicates.join("\n")))
+        }
+    }
+
+    pub fn blank(&self) -> SmolStr {
+        SmolStr::from(self.blank.as_str())
+    }
+
+    /// The number of tracks mentioned by the widest track tuple, 1 for a single-track program.
+    pub fn track_count(&self) -> usize {
+        self.transitions
+            .iter()
+            .flat_map(|((_, read), action)| vec![read, &action.write])
+            .map(|symbol| match symbol {
+                Symbol::Single(_) => 1,
+                Symbol::Tracks(tracks) => tracks.len(),
+            })
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// A program is multi-track as soon as any of its transitions mentions a track tuple.
+    pub fn is_multitrack(&self) -> bool {
+        self.transitions.iter().any(|((_, read), action)| {
+            matches!(read, Symbol::Tracks(_)) || matches!(action.write, Symbol::Tracks(_))
+        })
+    }
+
+    /// An enumerator has at least one print state.
+    pub fn is_enumerator(&self) -> bool {
+        !self.print.is_empty()
+    }
+
+    /// A transducer writes its result to the output tape rather than leaving it on the work tape.
+    pub fn is_transducer(&self) -> bool {
+        self.transitions
+            .iter()
+            .any(|(_, action)| action.output.is_some())
+    }
+
+    /// Looks for mistakes that parse fine but make the machine misbehave.
+    /// The diagnostics come sorted, errors first.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        if graphemes(&self.blank) > 1 {
+            diagnostics.push(Diagnostic::error(
+                format!("blank: {:?}", self.blank),
+                "The blank must be a single grapheme.",
+            ));
+        }
+
+        let mut outgoing: HashMap<&str, Vec<&Symbol>> = HashMap::new();
+        for ((state, read), _) in &self.transitions {
+            outgoing.entry(state.as_str()).or_default().push(read);
+        }
+
+        let halts = |state: &str| {
+            !matches!(
+                self.convert_goto(state.into()),
+                Goto::Run(_) | Goto::Emit(_)
+            )
+        };
+        if !outgoing.contains_key(self.initial.as_str()) && !halts(&self.initial) {
+            diagnostics.push(Diagnostic::error(
+                format!("initial: {:?}", self.initial),
+                "The initial state has no transitions, so every input is rejected.",
+            ));
+        }
+
+        for ((state, read), action) in &self.transitions {
+            let key = quote_key(state, read);
+            for &(role, symbol) in &[("reads", read), ("writes", &action.write)] {
+                if role == "reads" && self.is_class(symbol) {
+                    continue;
+                }
+                for s in symbol.symbols() {
+                    if s != self.blank && !self.is_wildcard(s) && graphemes(s) != 1 {
+                        diagnostics.push(Diagnostic::error(
+                            key.clone(),
+                            format!("{} {:?}, which is not a single grapheme.", role, s),
+                        ));
+                    }
+                }
+            }
+            if let Goto::Run(goto) | Goto::Emit(goto) = self.convert_goto(action.goto.clone()) {
+                if !outgoing.contains_key(goto.as_str()) {
+                    diagnostics.push(Diagnostic::warning(
+                        key,
+                        format!(
+                            "goes to {:?}, which has no transitions and so rejects.",
+                            goto
+                        ),
+                    ));
+                }
+            }
+        }
+
+        diagnostics.extend(self.check_declarations());
+
+        for (name, class) in &self.classes {
+            for symbol in class {
+                if graphemes(symbol) != 1 {
+                    diagnostics.push(Diagnostic::error(
+                        format!("classes: {:?}", name),
+                        format!("{:?} is not a single grapheme.", symbol),
+                    ));
+                }
+            }
+        }
+
+        for state in &self.accept {
+            if self.reject.contains(state) {
+                diagnostics.push(Diagnostic::error(
+                    format!("accept: {:?}", state),
+                    "A state can't both accept and reject.",
+                ));
+            }
+        }
+        for ((state, read), _) in &self.transitions {
+            if self.accept.contains(state) || self.reject.contains(state) {
+                diagnostics.push(Diagnostic::warning(
+                    quote_key(state, read),
+                    format!("{:?} halts, so this transition is never taken.", state),
+                ));
+            }
+        }
+
+        let mut reached = HashSet::new();
+        let mut todo = VecDeque::from(vec![self.initial.as_str()]);
+        while let Some(state) = todo.pop_front() {
+            // A halting state never takes its transitions.
+            if !reached.insert(state) || halts(state) {
+                continue;
+            }
+            for ((from, _), action) in &self.transitions {
+                if from == state {
+                    todo.push_back(action.goto.as_str());
+                }
+            }
+        }
+        for ((state, read), _) in &self.transitions {
+            if !reached.contains(state.as_str()) {
+                diagnostics.push(Diagnostic::warning(
+                    quote_key(state, read),
+                    format!("{:?} is never reached from the initial state.", state),
+                ));
+            }
+        }
+
+        diagnostics.sort();
+        diagnostics
+    }
+
+    /// Checks the transitions only use declared states and symbols.
+    fn check_declarations(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let is_state = |state: &str| match &self.states {
+            Some(states) => states.iter().any(|s| s == state),
+            None => true,
+        };
+        let is_symbol = |symbol: &str| match &self.tape_alphabet {
+            Some(alphabet) => {
+                symbol == self.blank
+                    || self.is_wildcard(symbol)
+                    || alphabet.iter().any(|s| s == symbol)
+            }
+            None => true,
+        };
+
+        if !is_state(&self.initial) {
+            diagnostics.push(Diagnostic::error(
+                format!("initial: {:?}", self.initial),
+                "The initial state is not among the declared states.",
+            ));
+        }
+        for state in &self.print {
+            if !is_state(state) {
+                diagnostics.push(Diagnostic::error(
+                    format!("print: {:?}", state),
+                    "This print state is not among the declared states.",
+                ));
+            }
+        }
+        let mut classes = self.classes.iter().collect::<Vec<_>>();
+        classes.sort();
+        for (name, class) in classes {
+            for symbol in class {
+                if !is_symbol(symbol) {
+                    diagnostics.push(Diagnostic::error(
+                        format!("classes: {:?}", name),
+                        format!("{:?} is not in the tape alphabet.", symbol),
+                    ));
+                }
+            }
+        }
+        for symbol in self.input_alphabet.iter().flatten() {
+            let key = format!("input_alphabet: {:?}", symbol);
+            if *symbol == self.blank {
+                diagnostics.push(Diagnostic::error(
+                    key,
+                    "The blank can't be part of the input alphabet.",
+                ));
+            } else if !is_symbol(symbol) {
+                diagnostics.push(Diagnostic::error(
+                    key,
+                    "The input alphabet must be part of the tape alphabet.",
+                ));
+            }
+        }
+
+        for ((state, read), action) in &self.transitions {
+            let key = quote_key(state, read);
+            if !is_state(state) {
+                diagnostics.push(Diagnostic::error(
+                    key.clone(),
+                    format!("{:?} is not a declared state.", state),
+                ));
+            }
+            if let Goto::Run(goto) | Goto::Emit(goto) = self.convert_goto(action.goto.clone()) {
+                if !is_state(&goto) {
+                    diagnostics.push(Diagnostic::error(
+                        key.clone(),
+                        format!("goes to {:?}, which is not a declared state.", goto),
+                    ));
+                }
+            }
+            for &(role, symbol) in &[("reads", read), ("writes", &action.write)] {
+                if role == "reads" && self.is_class(symbol) {
+                    continue;
+                }
+                for s in symbol.symbols() {
+                    if !is_symbol(s) {
+                        diagnostics.push(Diagnostic::error(
+                            key.clone(),
+                            format!("{} {:?}, which is not in the tape alphabet.", role, s),
+                        ));
+                    }
+                }
+            }
+        }
+        diagnostics
+    }
+
+    fn is_wildcard(&self, symbol: &str) -> bool {
+        self.wildcard.as_deref() == Some(symbol)
+    }
+
+    /// Single-track transitions can read a class by its name.
+    fn is_class(&self, read: &Symbol) -> bool {
+        match read {
+            Symbol::Single(s) => self.classes.contains_key(s),
+            Symbol::Tracks(_) => false,
+        }
+    }
+
+    fn convert_goto(&self, goto: String) -> Goto<SmolStr> {
+        if self.accept.contains(&goto) {
+            Goto::Halt(true)
+        } else if self.reject.contains(&goto) {
+            Goto::Halt(false)
+        } else if self.print.contains(&goto) {
+            Goto::Emit(SmolStr::from(goto))
+        } else {
+            Goto::Run(SmolStr::from(goto))
+        }
+    }
+
+    /// A machine that starts in a halting state halts on its first step, whatever it reads.
+    fn halting_initial(&self) -> Option<Goto<SmolStr>> {
+        match self.convert_goto(self.initial.clone()) {
+            halt @ Goto::Halt(_) => Some(halt),
+            _ => None,
+        }
+    }
+
+    fn convert_write(&self, symbol: String) -> Write<SmolStr> {
+        if self.is_wildcard(&symbol) {
+            Write::Same
+        } else {
+            Write::Symbol(SmolStr::from(symbol))
+        }
+    }
+
+    fn convert_tracks(&self, symbol: Symbol) -> TrackPattern<SmolStr> {
+        let tracks = match symbol {
+            Symbol::Single(s) => vec![s],
+            Symbol::Tracks(tracks) => tracks,
+        };
+        tracks
+            .into_iter()
+            .map(|s| {
+                if self.is_wildcard(&s) {
+                    None
+                } else {
+                    Some(SmolStr::from(s))
+                }
+            })
+            .collect()
+    }
+
+    pub fn into_single_track(self) -> Result<program::Program<SmolStr, SmolStr>, ron::de::Error> {
+        let (initial, prog_builder) = self.into_builder()?;
+        Ok(prog_builder.build(initial))
+    }
+
+    /// Like `into_single_track`, but stops short of building the table.
+    pub fn into_builder(
+        mut self,
+    ) -> Result<(SmolStr, ProgramBuilder<SmolStr, SmolStr>), ron::de::Error> {
+        let initial = SmolStr::from(self.initial.as_str());
+        let halting_initial = self.halting_initial();
+
+        let mut prog_builder = ProgramBuilder::new();
+        if let Some(halt) = &halting_initial {
+            prog_builder
+                .add_wildcard_transition(initial.clone(), (halt.clone(), Write::Same, None));
+        }
+        for ((state, read), action) in mem::take(&mut self.transitions) {
+            if halting_initial.is_some() && state == initial {
+                continue;
+            }
+            let (read, write, output) = match (read, action.write, action.output) {
+                (Symbol::Single(read), Symbol::Single(write), None) => (read, write, None),
+                (Symbol::Single(read), Symbol::Single(write), Some(Symbol::Single(output))) => {
+                    (read, write, Some(output))
+                }
+                _ => {
+                    return Err(ron::de::Error::Message(format!(
+                        "Transition out of {:?} uses tracks in a single-track program.",
+                        state
+                    )))
+                }
+            };
+            let state = SmolStr::from(state);
+            let goto = self.convert_goto(action.goto);
+            let response = (
+                goto,
+                self.convert_write(write),
+                convert_movement(action.mv),
+                output.map(|output| self.convert_write(output)),
+            );
+
+            if self.is_wildcard(&read) {
+                prog_builder.add_wildcard_transition(state, response);
+            } else if let Some(class) = self.classes.get(&read) {
+                let class = class.iter().map(|s| SmolStr::from(s.as_str()));
+                prog_builder.add_class_transition(state, class, response);
+            } else {
+                let read = SmolStr::from(read);
+                let response = program::Response::from(response).resolve(&read);
+                prog_builder.add_transition((state, read), response);
+            }
+        }
+
+        Ok((initial, prog_builder))
+    }
+
+    /// A plain symbol in a multi-track program refers to the first track and leaves the others
+    /// as wildcards. Patterns that overlap without one being more specific are an error.
+    pub fn into_multitrack(
+        mut self,
+    ) -> Result<MultiTrackProgram<SmolStr, SmolStr>, ron::de::Error> {
+        let initial = SmolStr::from(self.initial.as_str());
+        let halting_initial = self.halting_initial();
+
+        let mut prog_builder = MultiTrackBuilder::new();
+        if let Some(halt) = &halting_initial {
+            prog_builder.add_transition((initial.clone(), vec![]), (halt.clone(), vec![], None));
+        }
+        for ((state, read), action) in mem::take(&mut self.transitions) {
+            if halting_initial.is_some() && state == initial {
+                continue;
+            }
+            let state = SmolStr::from(state);
+            let read = self.convert_tracks(read);
+            let goto = self.convert_goto(action.goto);
+            let write = self.convert_tracks(action.write);
+            let mv = convert_movement(action.mv);
+            let output = action.output.map(|output| self.convert_tracks(output));
+
+            prog_builder.add_transition((state, read), (goto, write, mv, output));
+        }
+
+        prog_builder
+            .build(initial)
+            .map_err(|e| ron::de::Error::Message(e.to_string()))
+    }
+}
+
+pub fn read_program<R>(rdr: R) -> Result<program::Program<SmolStr, SmolStr>, ron::de::Error>
+where
+    R: Read,
+{
+    ProgramFile::parse(rdr)?.into_single_track()
+}
+
+/// Reads which kind of machine a program is, in any syntax.
+pub fn read_kind(code: &str, syntax: Syntax) -> Result<Kind, ron::de::Error> {
+    let header: Header = syntax.deserialize(code)?;
+    Ok(header.kind)
+}
+
+pub fn read_dfa(code: &str, syntax: Syntax) -> Result<Dfa<SmolStr, SmolStr>, ron::de::Error> {
+    let prog: DfaProgram = syntax.deserialize(code)?;
+    let mut builder = DfaBuilder::new();
+    for ((state, read), goto) in prog.transitions.into_iter() {
+        builder.add_transition(
+            (SmolStr::from(state), SmolStr::from(read)),
+            SmolStr::from(goto),
+        );
+    }
+    for state in prog.accept {
+        builder.add_accepting(SmolStr::from(state));
+    }
+    Ok(builder.build(SmolStr::from(prog.initial)))
+}
+
+pub fn read_nfa(code: &str, syntax: Syntax) -> Result<Nfa<SmolStr, SmolStr>, ron::de::Error> {
+    let prog: NfaProgram = syntax.deserialize(code)?;
+    let mut builder = NfaBuilder::new();
+    for ((state, read), gotos) in prog.transitions.into_iter() {
+        let state = SmolStr::from(state);
+        let read = convert_epsilon(read);
+        for goto in gotos {
+            builder.add_transition((state.clone(), read.clone()), SmolStr::from(goto));
+        }
+    }
+    for state in prog.accept {
+        builder.add_accepting(SmolStr::from(state));
+    }
+    Ok(builder.build(SmolStr::from(prog.initial)))
+}
+
+pub fn read_pda(code: &str, syntax: Syntax) -> Result<Pda<SmolStr, SmolStr>, ron::de::Error> {
+    let prog: PdaProgram = syntax.deserialize(code)?;
+    let mut builder = PdaBuilder::new();
+    for ((state, read, pop), moves) in prog.transitions.into_iter() {
+        let state = SmolStr::from(state);
+        let read = (convert_epsilon(read), convert_epsilon(pop));
+        for (goto, push) in moves {
+            let push = push.into_iter().map(SmolStr::from).collect();
+            builder.add_transition((state.clone(), read.clone()), (SmolStr::from(goto), push));
+        }
+    }
+    for state in prog.accept {
+        builder.add_accepting(SmolStr::from(state));
+    }
+    Ok(builder.build(SmolStr::from(prog.initial)))
+}
+
+fn convert_epsilon(symbol: String) -> Option<SmolStr> {
+    match symbol.as_str() {
+        EPSILON => None,
+        s => Some(SmolStr::from(s)),
+    }
+}
+
+impl Symbol {
+    fn symbols(&self) -> Vec<&str> {
+        match self {
+            Symbol::Single(s) => vec![s.as_str()],
+            Symbol::Tracks(tracks) => tracks.iter().map(String::as_str).collect(),
+        }
+    }
+
+    fn substitute(&self, binding: &[(&str, &str)]) -> Self {
+        match self {
+            Symbol::Single(s) => Symbol::Single(substitute(s, binding)),
+            Symbol::Tracks(tracks) => {
+                Symbol::Tracks(tracks.iter().map(|s| substitute(s, binding)).collect())
+            }
+        }
+    }
+
+    fn to_ron(&self) -> String {
+        match self {
+            Symbol::Single(s) => ron_string(s),
+            Symbol::Tracks(tracks) => {
+                let tracks = tracks.iter().map(|s| ron_string(s)).collect::<Vec<_>>();
+                format!("({})", tracks.join(", "))
+            }
+        }
+    }
+
+    fn to_yaml(&self) -> String {
+        match self {
+            Symbol::Single(s) => yaml_string(s),
+            Symbol::Tracks(tracks) => {
+                let tracks = tracks.iter().map(|s| yaml_string(s)).collect::<Vec<_>>();
+                format!("[{}]", tracks.join(", "))
+            }
+        }
+    }
+
+    fn quote(&self) -> String {
+        match self {
+            Symbol::Single(s) => format!("{:?}", s),
+            Symbol::Tracks(tracks) => {
+                let tracks = tracks
+                    .iter()
+                    .map(|s| format!("{:?}", s))
+                    .collect::<Vec<_>>();
+                format!("({})", tracks.join(", "))
+            }
+        }
+    }
+}
+
+/// An input symbol outside the declared input alphabet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnexpectedInput {
+    pub position: usize,
+    pub symbol: SmolStr,
+}
+
+impl fmt::Display for UnexpectedInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Input symbol {:?} at position {} is not in the input alphabet.",
+            self.symbol, self.position
+        )
+    }
+}
+
+impl Error for UnexpectedInput {}
+
+impl Action {
+    fn substitute(&self, binding: &[(&str, &str)]) -> Self {
+        Self {
+            goto: substitute(&self.goto, binding),
+            write: self.write.substitute(binding),
+            mv: self.mv.clone(),
+            output: self
+                .output
+                .as_ref()
+                .map(|output| output.substitute(binding)),
+        }
+    }
+
+    fn quote(&self) -> String {
+        let mut quoted = format!("({:?}, {}, {:?}", self.goto, self.write.quote(), self.mv);
+        if let Some(output) = &self.output {
+            quoted.push_str(&format!(", {}", output.quote()));
+        }
+        quoted.push(')');
+        quoted
+    }
+}
+
+impl Syntax {
+    /// Anything without a known extension is taken to be RON.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Syntax::Json,
+            Some("yaml") | Some("yml") => Syntax::Yaml,
+            Some("toml") => Syntax::Toml,
+            _ => Syntax::Ron,
+        }
+    }
+
+    /// Reports errors as RON's, so every syntax fails the same way.
+    fn deserialize<T>(self, code: &str) -> Result<T, ron::de::Error>
+    where
+        T: DeserializeOwned,
+    {
+        let error = |e: &dyn fmt::Display| ron::de::Error::Message(e.to_string());
+        match self {
+            Syntax::Ron => {
+                ENTRY_LINES.with(|lines| *lines.borrow_mut() = entry_lines(code));
+                let result = ron::de::from_str(code);
+                ENTRY_LINES.with(|lines| lines.borrow_mut().clear());
+                result
+            }
+            Syntax::Json => serde_json::from_str(code).map_err(|e| error(&e)),
+            Syntax::Yaml => serde_yaml::from_str(code).map_err(|e| error(&e)),
+            Syntax::Toml => toml::from_str(code).map_err(|e| error(&e)),
+        }
+    }
+}
+
+thread_local! {
+    /// The line each transition of the RON program being read starts on, since RON's errors
+    /// carry no position for the duplicate check to report.
+    static ENTRY_LINES: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Lets an optional field be written without RON's `Some(...)`.
+fn declared<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// Deserializes an automaton's transitions from a map, or from a list of objects holding the
+/// key's fields and then the value's, for syntaxes whose maps can't have tuples as keys.
+fn table<'de, D, T, K, V>(deserializer: D) -> Result<Vec<(K, V)>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Into<(K, V)>,
+    K: Deserialize<'de> + Eq + Hash + fmt::Debug,
+    V: Deserialize<'de>,
+{
+    struct TableVisitor<T, K, V>(PhantomData<(T, K, V)>);
+
+    impl<'de, T, K, V> Visitor<'de> for TableVisitor<T, K, V>
+    where
+        T: Deserialize<'de> + Into<(K, V)>,
+        K: Deserialize<'de> + Eq + Hash + fmt::Debug,
+        V: Deserialize<'de>,
+    {
+        type Value = Vec<(K, V)>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "a map or list of transitions")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let mut table = Vec::new();
+            while let Some(transition) = seq.next_element::<T>()? {
+                table.push(transition.into());
+            }
+            check_table(&table)?;
+            Ok(table)
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+        where
+            A: MapAccess<'de>,
+        {
+            let mut table = Vec::new();
+            while let Some(entry) = map.next_entry()? {
+                table.push(entry);
+            }
+            check_table(&table)?;
+            Ok(table)
+        }
+    }
+
+    deserializer.deserialize_any(TableVisitor::<T, K, V>(PhantomData))
+}
+
+fn check_table<K, V, E>(table: &[(K, V)]) -> Result<(), E>
+where
+    K: Eq + Hash + fmt::Debug,
+    E: serde::de::Error,
+{
+    let mut seen = HashSet::new();
+    match table.iter().find(|(key, _)| !seen.insert(key)) {
+        Some((key, _)) => Err(E::custom(format!("Duplicate transition for {:?}.", key))),
+        None => Ok(()),
+    }
+}
+
+/// Deserializes a map into its entries in the order they were written, failing on a duplicate
+/// key. A list of `Transition` objects gives the same entries.
+fn entries<'de, D>(deserializer: D) -> Result<Vec<Entry>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct EntriesVisitor;
+
+    impl<'de> Visitor<'de> for EntriesVisitor {
+        type Value = Vec<Entry>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "a map or list of transitions")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let mut entries = Entries::default();
+            while let Some(entry) = seq.next_element_seed(TransitionSeed(&entries))? {
+                entries.push(entry);
+            }
+            Ok(entries.entries)
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+        where
+            A: MapAccess<'de>,
+        {
+            let mut entries = Entries::default();
+            while let Some(key) = map.next_key()? {
+                let entry = map.next_value_seed(ActionSeed(&entries, key))?;
+                entries.push(entry);
+            }
+            Ok(entries.entries)
+        }
+    }
+
+    /// The entries read so far, and where each key first appeared.
+    #[derive(Default)]
+    struct Entries {
+        entries: Vec<Entry>,
+        seen: HashMap<(String, Symbol), usize>,
+    }
+
+    impl Entries {
+        fn push(&mut self, entry: Entry) {
+            self.seen.insert(entry.0.clone(), self.entries.len());
+            self.entries.push(entry);
+        }
+
+        /// Fails inside the visitor of the entry that repeats a key, so syntaxes that track
+        /// positions report that entry's.
+        fn check<E: serde::de::Error>(&self, entry: Entry) -> Result<Entry, E> {
+            match self.seen.get(&entry.0) {
+                Some(&first) => Err(E::custom(format!(
+                    "Duplicate transition:\n    {}\n    {}",
+                    locate_entry(first, &self.entries[first]),
+                    locate_entry(self.entries.len(), &entry)
+                ))),
+                None => Ok(entry),
+            }
+        }
+    }
+
+    /// Reads a `Transition` object.
+    struct TransitionSeed<'a>(&'a Entries);
+
+    impl<'de> DeserializeSeed<'de> for TransitionSeed<'_> {
+        type Value = Entry;
+
+        fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_struct("Transition", TRANSITION_FIELDS, self)
+        }
+    }
+
+    impl<'de> Visitor<'de> for TransitionSeed<'_> {
+        type Value = Entry;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "a transition")
+        }
+
+        fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let t = Transition::deserialize(SeqAccessDeserializer::new(seq))?;
+            self.0.check(t.into_entry())
+        }
+
+        fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+        where
+            A: MapAccess<'de>,
+        {
+            let t = Transition::deserialize(MapAccessDeserializer::new(map))?;
+            self.0.check(t.into_entry())
+        }
+    }
+
+    /// Reads the response to a key of the map form.
+    struct ActionSeed<'a>(&'a Entries, (String, Symbol));
+
+    impl<'de> DeserializeSeed<'de> for ActionSeed<'_> {
+        type Value = Entry;
+
+        fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_tuple(4, self)
+        }
+    }
+
+    impl<'de> Visitor<'de> for ActionSeed<'_> {
+        type Value = Entry;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "a (goto, write, move) response")
+        }
+
+        fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let action = Action::deserialize(SeqAccessDeserializer::new(seq))?;
+            self.0.check((self.1, action))
+        }
+    }
+
+    deserializer.deserialize_any(EntriesVisitor)
+}
+
+const TRANSITION_FIELDS: &[&str] = &["state", "read", "goto", "write", "move", "output"];
+
+fn describe_entry(((state, read), action): &Entry) -> String {
+    format!("{}: {}", quote_key(state, read), action.quote())
+}
+
+/// Describes the entry at `index`, with its line when the span pass found one.
+fn locate_entry(index: usize, entry: &Entry) -> String {
+    match ENTRY_LINES.with(|lines| lines.borrow().get(index).copied()) {
+        Some(line) => format!("{} on line {}", describe_entry(entry), line),
+        None => describe_entry(entry),
+    }
+}
+
+/// Finds the line each entry of a RON program's `transitions` starts on, in order, whether
+/// they're written as a map or a list.
+fn entry_lines(code: &str) -> Vec<usize> {
+    let mut lines = Vec::new();
+    let mut chars = code.chars().peekable();
+    let mut line = 1;
+    let mut depth = 0;
+    let mut word = String::new();
+    // Set once `transitions:` is seen at the top level, until its map or list opens.
+    let mut awaiting_table = false;
+    let mut table_depth = None;
+    let mut expecting_entry = false;
+
+    while let Some(c) = chars.next() {
+        if c.is_alphanumeric() || c == '_' {
+            if c == 'r' && word.is_empty() && matches!(chars.peek(), Some('"') | Some('#')) {
+                // A raw string, `r#"..."#`.
+                let mut hashes = 0;
+                while chars.peek() == Some(&'#') {
+                    chars.next();
+                    hashes += 1;
+                }
+                chars.next();
+                record_entry(&mut expecting_entry, &mut lines, line);
+                'raw: while let Some(c) = chars.next() {
+                    if c == '\n' {
+                        line += 1;
+                    } else if c == '"' {
+                        for _ in 0..hashes {
+                            if chars.peek() != Some(&'#') {
+                                continue 'raw;
+                            }
+                            chars.next();
+                        }
+                        break;
+                    }
+                }
+                continue;
+            }
+            word.push(c);
+            record_entry(&mut expecting_entry, &mut lines, line);
+            continue;
+        }
+        if c == ':' && depth == 1 && word == "transitions" {
+            awaiting_table = true;
+        }
+        word.clear();
+        match c {
+            '\n' => line += 1,
+            '"' | '\'' => {
+                record_entry(&mut expecting_entry, &mut lines, line);
+                while let Some(d) = chars.next() {
+                    match d {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '\n' => line += 1,
+                        d if d == c => break,
+                        _ => {}
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                for d in &mut chars {
+                    if d == '\n' {
+                        line += 1;
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+                for d in &mut chars {
+                    if d == '\n' {
+                        line += 1;
+                    }
+                    if last == '*' && d == '/' {
+                        break;
+                    }
+                    last = d;
+                }
+            }
+            '(' | '[' | '{' => {
+                if awaiting_table && c != '(' {
+                    awaiting_table = false;
+                    table_depth = Some(depth + 1);
+                    expecting_entry = true;
+                } else {
+                    record_entry(&mut expecting_entry, &mut lines, line);
+                }
+                depth += 1;
+            }
+            ')' | ']' | '}' => {
+                if table_depth == Some(depth) {
+                    return lines;
+                }
+                depth -= 1;
+            }
+            ',' if table_depth == Some(depth) => expecting_entry = true,
+            c if c.is_whitespace() => {}
+            _ => record_entry(&mut expecting_entry, &mut lines, line),
+        }
+    }
+    lines
+}
+
+fn record_entry(expecting_entry: &mut bool, lines: &mut Vec<usize>, line: usize) {
+    if *expecting_entry {
+        *expecting_entry = false;
+        lines.push(line);
+    }
+}
 
-use crate::{
-    program,
-    program::{Goto, ProgramBuilder, TransitionFn},
-};
+/// Replaces each placeholder with the value it's bound to, in a single pass so a value that
+/// looks like a placeholder is left alone.
+fn substitute(s: &str, binding: &[(&str, &str)]) -> String {
+    let mut out = String::new();
+    let mut rest = s;
+    while let Some(c) = rest.chars().next() {
+        match binding
+            .iter()
+            .find(|(placeholder, _)| rest.starts_with(placeholder))
+        {
+            Some((placeholder, value)) => {
+                out.push_str(value);
+                rest = &rest[placeholder.len()..];
+            }
+            None => {
+                out.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    out
+}
 
-#[derive(Debug, Deserialize)]
-enum Movement {
-    Stay,
-    Left,
-    Right,
+/// Quotes a string as a double-quoted YAML scalar, whose escapes include all of JSON's.
+fn yaml_string(s: &str) -> String {
+    serde_json::Value::from(s).to_string()
 }
 
-#[derive(Debug, Deserialize)]
-struct Program {
-    initial: String,
-    transitions: HashMap<(String, String), (String, String, Movement)>,
+/// Quotes a string the way RON reads it back.
+fn ron_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
 }
 
-pub fn read_program<R>(
-    rdr: R,
-) -> Result<(SmolStr, impl TransitionFn<SmolStr, SmolStr>), ron::de::Error>
-where
-    R: Read,
-{
-    let prog: Program = ron::de::from_reader(rdr)?;
-    let initial = SmolStr::from(prog.initial);
-    let transitions = prog.transitions;
+fn quote_key(state: &str, read: &Symbol) -> String {
+    format!("({:?}, {})", state, read.quote())
+}
 
-    let mut prog_builder = ProgramBuilder::new();
-    for ((state, read), (goto, write, mv)) in transitions.into_iter() {
-        let state = SmolStr::from(state);
-        let read = SmolStr::from(read); // TODO: check grapheme count.
-        let goto = match goto.as_str() {
-            "accept" => Goto::Halt(true),
-            "reject" => Goto::Halt(false),
-            s => Goto::Run(SmolStr::from(s)),
-        };
-        let write = SmolStr::from(write);
-        let mv = match mv {
-            Movement::Stay => None,
-            Movement::Left => Some(program::Movement::Left),
-            Movement::Right => Some(program::Movement::Right),
-        };
+fn graphemes(s: &str) -> usize {
+    s.graphemes(true).count()
+}
 
-        prog_builder.add_transition((state, read), (goto, write, mv));
-    }
+fn default_accept() -> Vec<String> {
+    vec![String::from("accept")]
+}
+
+fn default_reject() -> Vec<String> {
+    vec![String::from("reject")]
+}
 
-    Ok((initial, prog_builder.build()))
+fn convert_movement(mv: Movement) -> Option<program::Movement> {
+    match mv {
+        Movement::Stay => None,
+        Movement::Left => Some(program::Movement::Left),
+        Movement::Right => Some(program::Movement::Right),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::program::Response;
+    use crate::{
+        multitrack::Tracks,
+        program::{Response, TransitionFn},
+    };
 
     #[test]
     fn degenerate_program() {
@@ -64,16 +1883,13 @@ mod tests {
     initial: "main",
     transitions: {}
 )"#;
-        let (initial, tr_func) =
+        let tr_func =
             read_program(code.as_bytes()).expect("Perfectly valid program with no transitions.");
+        let initial = tr_func.initial().clone();
         assert_eq!(initial.as_str(), "main");
         assert_eq!(
-            tr_func(&SmolStr::from("main"), &SmolStr::from("8")),
-            Response {
-                goto: Goto::Halt(false),
-                write: SmolStr::from("8"),
-                mv: None
-            }
+            tr_func.respond(&SmolStr::from("main"), &SmolStr::from("8")),
+            Response::new(Goto::Halt(false), SmolStr::from("8"), None)
         );
     }
 
@@ -107,39 +1923,722 @@ mod tests {
         ("three", ""): ("accept", "0", Left),
     },
 )"#;
-        let (init, tr_func) = read_program(code.as_bytes()).expect("Perfectly valid program.");
+        let tr_func = read_program(code.as_bytes()).expect("Perfectly valid program.");
+        let init = tr_func.initial();
         assert_eq!(init.as_str(), "one");
         assert_eq!(
-            tr_func(&SmolStr::from("one"), &SmolStr::from("0")),
-            Response {
-                goto: Goto::Run(SmolStr::from("two")),
-                write: SmolStr::from(""),
-                mv: None,
-            }
+            tr_func.respond(&SmolStr::from("one"), &SmolStr::from("0")),
+            Response::new(Goto::Run(SmolStr::from("two")), SmolStr::from(""), None)
+        );
+        assert_eq!(
+            tr_func.respond(&SmolStr::from("two"), &SmolStr::from("1")),
+            Response::new(
+                Goto::Halt(false),
+                SmolStr::from("0"),
+                Some(program::Movement::Right)
+            )
+        );
+        assert_eq!(
+            tr_func.respond(&SmolStr::from("three"), &SmolStr::from("")),
+            Response::new(
+                Goto::Halt(true),
+                SmolStr::from("0"),
+                Some(program::Movement::Left)
+            )
+        );
+        assert_eq!(
+            tr_func.respond(&SmolStr::from("three"), &SmolStr::from("unexpected")),
+            Response::new(Goto::Halt(false), SmolStr::from("unexpected"), None)
+        );
+    }
+
+    #[test]
+    fn tracks_in_single_track_program() {
+        let code = r#"Program(
+    initial: "one",
+    transitions: {
+        ("one", ("0", "*")): ("two", "", Stay),
+    },
+)"#;
+        let prog = ProgramFile::parse(code.as_bytes()).expect("Valid multi-track program.");
+        assert!(prog.is_multitrack());
+        assert!(prog.into_single_track().is_err());
+    }
+
+    #[test]
+    fn ambiguous_multitrack_program() {
+        let code = r#"Program(
+    initial: "one",
+    wildcard: "*",
+    transitions: {
+        ("one", ("0", "*")): ("two", ("*", "x"), Right),
+        ("one", ("*", "x")): ("accept", "0", Stay),
+    },
+)"#;
+        let prog = ProgramFile::parse(code.as_bytes()).expect("Valid multi-track program.");
+        assert!(prog.into_multitrack().is_err());
+    }
+
+    #[test]
+    fn multitrack_program() {
+        let code = r#"Program(
+    initial: "one",
+    wildcard: "*",
+    transitions: {
+        ("one", ("0", "*")): ("two", ("*", "x"), Right),
+        ("one", "1"): ("accept", "0", Stay),
+    },
+)"#;
+        let prog = ProgramFile::parse(code.as_bytes()).expect("Valid multi-track program.");
+        let tr_func = prog.into_multitrack().expect("Unambiguous patterns.");
+        assert_eq!(tr_func.initial().as_str(), "one");
+        let cell = |tracks: &[&str]| {
+            Tracks::from(tracks.iter().map(|&s| SmolStr::from(s)).collect::<Vec<_>>())
+        };
+        assert_eq!(
+            tr_func.respond(&SmolStr::from("one"), &cell(&["0", "y"])),
+            Response::new(
+                Goto::Run(SmolStr::from("two")),
+                cell(&["0", "x"]),
+                Some(program::Movement::Right)
+            )
+        );
+        assert_eq!(
+            tr_func.respond(&SmolStr::from("one"), &cell(&["1", "y"])),
+            Response::new(Goto::Halt(true), cell(&["0", "y"]), None)
         );
+    }
+
+    #[test]
+    fn blank() {
+        let code = r#"Program(initial: "main", transitions: {})"#;
+        let prog = ProgramFile::parse(code.as_bytes()).expect("Blank is optional.");
+        assert_eq!(prog.blank().as_str(), "");
+        let code = r#"Program(initial: "main", blank: "_", transitions: {})"#;
+        let prog = ProgramFile::parse(code.as_bytes()).expect("Perfectly valid program.");
+        assert_eq!(prog.blank().as_str(), "_");
+    }
+
+    #[test]
+    fn transducer() {
+        let code = r#"Program(
+    initial: "one",
+    transitions: {
+        ("one", "0"): ("one", "0", Right, "1"),
+        ("one", ""): ("accept", "", Stay),
+    },
+)"#;
+        let prog = ProgramFile::parse(code.as_bytes()).expect("Valid transducer.");
+        assert!(prog.is_transducer());
+        let tr_func = prog.into_single_track().expect("Valid transducer.");
         assert_eq!(
-            tr_func(&SmolStr::from("two"), &SmolStr::from("1")),
+            tr_func.respond(&SmolStr::from("one"), &SmolStr::from("0")),
             Response {
-                goto: Goto::Halt(false),
+                goto: Goto::Run(SmolStr::from("one")),
                 write: SmolStr::from("0"),
                 mv: Some(program::Movement::Right),
+                output: Some(SmolStr::from("1")),
             }
         );
+    }
+
+    #[test]
+    fn print_states() {
+        let code = r#"Program(
+    initial: "one",
+    print: ["two"],
+    transitions: {
+        ("one", ""): ("two", "", Right, "a"),
+    },
+)"#;
+        let prog = ProgramFile::parse(code.as_bytes()).expect("Valid enumerator.");
+        assert!(prog.is_enumerator());
+        let tr_func = prog.into_single_track().expect("Valid enumerator.");
         assert_eq!(
-            tr_func(&SmolStr::from("three"), &SmolStr::from("")),
-            Response {
-                goto: Goto::Halt(true),
-                write: SmolStr::from("0"),
-                mv: Some(program::Movement::Left),
-            }
+            tr_func
+                .respond(&SmolStr::from("one"), &SmolStr::from(""))
+                .goto,
+            Goto::Emit(SmolStr::from("two"))
         );
+    }
+
+    #[test]
+    fn kinds() {
+        let code = r#"Program(initial: "main", transitions: {})"#;
         assert_eq!(
-            tr_func(&SmolStr::from("three"), &SmolStr::from("unexpected")),
-            Response {
-                goto: Goto::Halt(false),
-                write: SmolStr::from("unexpected"),
-                mv: None,
-            }
+            read_kind(code, Syntax::Ron).expect("Kind is optional."),
+            Kind::Turing
+        );
+        let code = r#"Program(kind: Pda, initial: "main", accept: [], transitions: {})"#;
+        assert_eq!(read_kind(code, Syntax::Ron).expect("Valid PDA."), Kind::Pda);
+    }
+
+    #[test]
+    fn dfa() {
+        let code = r#"Program(
+    kind: Dfa,
+    initial: "even",
+    accept: ["even"],
+    transitions: {
+        ("even", "0"): "odd",
+        ("odd", "0"): "even",
+    },
+)"#;
+        let dfa = read_dfa(code, Syntax::Ron).expect("Valid DFA.");
+        assert!(dfa.accepts(vec![SmolStr::from("0"), SmolStr::from("0")]));
+        assert!(!dfa.accepts(vec![SmolStr::from("0")]));
+    }
+
+    #[test]
+    fn nfa() {
+        let code = r#"Program(
+    kind: Nfa,
+    initial: "start",
+    accept: ["a"],
+    transitions: {
+        ("start", ""): ["skip", "a"],
+        ("skip", "b"): ["start"],
+        ("a", "a"): ["a"],
+    },
+)"#;
+        let nfa = read_nfa(code, Syntax::Ron).expect("Valid NFA.");
+        let input = |s: &str| {
+            s.chars()
+                .map(|c| SmolStr::from(c.to_string()))
+                .collect::<Vec<_>>()
+        };
+        assert!(nfa.accepts(input("bba")));
+        assert!(!nfa.accepts(input("bab")));
+    }
+
+    #[test]
+    fn pda() {
+        let code = r#"Program(
+    kind: Pda,
+    initial: "start",
+    accept: ["done"],
+    transitions: {
+        // Mark the bottom of the stack, so "done" is only reached once every bracket closed.
+        ("start", "", ""): [("open", ["$"])],
+        ("open", "(", ""): [("open", ["("])],
+        ("open", ")", "("): [("open", [])],
+        ("open", "", "$"): [("done", [])],
+    },
+)"#;
+        let pda = read_pda(code, Syntax::Ron).expect("Valid PDA.");
+        let input = |s: &str| {
+            s.chars()
+                .map(|c| SmolStr::from(c.to_string()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(pda.accepts(input("(()())"), 1000), Some(true));
+        assert_eq!(pda.accepts(input("(()"), 1000), Some(false));
+        assert_eq!(pda.accepts(input(""), 1000), Some(true));
+        assert_eq!(pda.accepts(input("())"), 1000), Some(false));
+    }
+
+    #[test]
+    fn validate_examples() {
+        for example in &["examples/hello.ron", "examples/w#w.ron"] {
+            let code = std::fs::read_to_string(example).expect("Example exists.");
+            let prog = ProgramFile::parse(code.as_bytes()).expect("Example is valid.");
+            assert_eq!(prog.validate(), vec![], "{}", example);
+        }
+    }
+
+    #[test]
+    fn validate() {
+        let code = r#"Program(
+    initial: "start",
+    transitions: {
+        ("start", "ab"): ("middle", "x", Right),
+        ("start", "a"): ("end", "é", Right),
+        ("lost", "a"): ("start", "a", Stay),
+    },
+)"#;
+        let prog = ProgramFile::parse(code.as_bytes()).expect("Parses fine.");
+        let messages = prog
+            .validate()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                r#"error: ("start", "ab"): reads "ab", which is not a single grapheme."#,
+                r#"warning: ("lost", "a"): "lost" is never reached from the initial state."#,
+                r#"warning: ("start", "a"): goes to "end", which has no transitions and so rejects."#,
+                r#"warning: ("start", "ab"): goes to "middle", which has no transitions and so rejects."#,
+            ]
+        );
+
+        let code = r#"Program(initial: "start", blank: "__", transitions: {})"#;
+        let prog = ProgramFile::parse(code.as_bytes()).expect("Parses fine.");
+        assert_eq!(prog.validate().len(), 2);
+    }
+
+    #[test]
+    fn duplicate_transitions() {
+        let code = r#"Program(
+    initial: "start",
+    transitions: {
+        ("start", "a"): ("start", "b", Right),
+        // ("start", "a") is fine in a comment.
+        ("start", "b"): ("start", "a", Right),
+        ("start", "a"): ("accept", "a", Stay),
+    },
+)"#;
+        let err = ProgramFile::parse(code.as_bytes()).expect_err("Duplicate key.");
+        assert_eq!(
+            err.to_string(),
+            r#"Duplicate transition:
+    ("start", "a"): ("start", "b", Right) on line 4
+    ("start", "a"): ("accept", "a", Stay) on line 7"#
+        );
+
+        let code = r##"Program(
+    initial: "start",
+    transitions: [
+        (state: "start", read: "a", goto: "start", write: "b", move: Right),
+        /* (state: "start", read: "a"), */ (
+            state: "start", read: r#"a"#, goto: "accept", write: "a", move: Stay,
+        ),
+    ],
+)"##;
+        let err = ProgramFile::parse(code.as_bytes()).expect_err("Duplicate key.");
+        assert!(err.to_string().contains("on line 4\n"), "{}", err);
+        assert!(err.to_string().ends_with("on line 5"), "{}", err);
+
+        // Other syntaxes say where the second entry is.
+        let code = r#"{
+    "initial": "start",
+    "transitions": [
+        {"state": "start", "read": "a", "goto": "start", "write": "b", "move": "Right"},
+        {"state": "start", "read": "a", "goto": "accept", "write": "a", "move": "Stay"}
+    ]
+}"#;
+        let err = ProgramFile::parse_as(code.as_bytes(), Syntax::Json).expect_err("Duplicate key.");
+        assert!(err.to_string().contains("line 5"), "{}", err);
+    }
+
+    #[test]
+    fn declarations() {
+        let code = r#"Program(
+    initial: "start",
+    blank: "_",
+    states: ["start", "done"],
+    input_alphabet: ["a", "b"],
+    tape_alphabet: ["a", "x"],
+    wildcard: "*",
+    transitions: {
+        ("start", "a"): ("done", "x", Right),
+        ("start", "_"): ("accept", "_", Stay),
+        ("done", "y"): ("other", "a", Stay),
+        ("done", "x"): ("accept", "*", Stay),
+    },
+)"#;
+        let prog = ProgramFile::parse(code.as_bytes()).expect("Parses fine.");
+        let messages = prog
+            .validate()
+            .into_iter()
+            .filter(Diagnostic::is_error)
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                r#"error: ("done", "y"): goes to "other", which is not a declared state."#,
+                r#"error: ("done", "y"): reads "y", which is not in the tape alphabet."#,
+                r#"error: input_alphabet: "b": The input alphabet must be part of the tape alphabet."#,
+            ]
+        );
+
+        assert_eq!(prog.check_input(&["a", "a"]), Ok(()));
+        assert_eq!(
+            prog.check_input(&["a", "_"]),
+            Err(UnexpectedInput {
+                position: 1,
+                symbol: SmolStr::from("_"),
+            })
+        );
+    }
+
+    #[test]
+    fn halting_states() {
+        let code = r#"Program(
+    initial: "accept",
+    accept: ["q_acc", "halt"],
+    reject: ["qR"],
+    transitions: {
+        ("accept", "0"): ("q_acc", "0", Stay),
+        ("accept", "1"): ("halt", "1", Stay),
+        ("accept", "2"): ("qR", "2", Stay),
+    },
+)"#;
+        let prog = ProgramFile::parse(code.as_bytes()).expect("Parses fine.");
+        assert_eq!(prog.validate(), vec![]);
+        let prog = prog.into_single_track().expect("Single track.");
+        let init = prog.initial().clone();
+        assert_eq!(init, "accept");
+        assert_eq!(
+            prog.respond(&init, &SmolStr::from("0")).goto,
+            Goto::Halt(true)
+        );
+        assert_eq!(
+            prog.respond(&init, &SmolStr::from("1")).goto,
+            Goto::Halt(true)
+        );
+        assert_eq!(
+            prog.respond(&init, &SmolStr::from("2")).goto,
+            Goto::Halt(false)
+        );
+    }
+
+    #[test]
+    fn halting_initial_state() {
+        for (halting, accept) in &[
+            (r#"accept: ["done"]"#, true),
+            (r#"reject: ["done"]"#, false),
+        ] {
+            let code = format!(
+                r#"Program(initial: "done", {}, transitions: {{}})"#,
+                halting
+            );
+            let prog = ProgramFile::parse(code.as_bytes()).expect("Parses fine.");
+            assert_eq!(prog.validate(), vec![]);
+            let prog = prog.into_single_track().expect("Single track.");
+            let init = prog.initial().clone();
+            assert_eq!(
+                prog.respond(&init, &SmolStr::from("0")).goto,
+                Goto::Halt(*accept)
+            );
+        }
+    }
+
+    #[test]
+    fn classes_and_wildcards() {
+        let code = r#"Program(
+    initial: "skip",
+    blank: "_",
+    classes: {"digits": ["0", "1"]},
+    wildcard: "*",
+    transitions: {
+        ("skip", "digits"): ("skip", "*", Right),
+        ("skip", "1"): ("skip", "x", Right),
+        ("skip", "*"): ("accept", "!", Stay),
+    },
+)"#;
+        let prog = ProgramFile::parse(code.as_bytes()).expect("Parses fine.");
+        assert_eq!(prog.validate(), vec![]);
+        let prog = prog.into_single_track().expect("Single track.");
+        let init = prog.initial().clone();
+        let respond = |read: &str| prog.respond(&init, &SmolStr::from(read));
+        assert_eq!(respond("0").write, "0");
+        assert_eq!(respond("0").goto, Goto::Run(SmolStr::from("skip")));
+        assert_eq!(respond("1").write, "x");
+        assert_eq!(respond("_").write, "!");
+        assert_eq!(respond("_").goto, Goto::Halt(true));
+
+        // Without a declared wildcard, "*" is just a symbol.
+        let code = code.replace("    wildcard: \"*\",\n", "");
+        let prog = ProgramFile::parse(code.as_bytes()).expect("Parses fine.");
+        let prog = prog.into_single_track().expect("Single track.");
+        let respond = |read: &str| prog.respond(&init, &SmolStr::from(read));
+        assert_eq!(respond("0").write, "*");
+        assert_eq!(respond("*").goto, Goto::Halt(true));
+        assert_eq!(respond("_").goto, Goto::Halt(false));
+    }
+
+    #[test]
+    fn templates() {
+        let code = r#"Program(
+    initial: "read",
+    classes: {"bits": ["0", "1"]},
+    parameters: {"b": "bits"},
+    transitions: {
+        ("read", "{b}"): ("carry {b}", "_", Right),
+        ("carry {b}", "_"): ("accept", "{b}", Stay),
+    },
+)"#;
+        let prog = ProgramFile::parse(code.as_bytes()).expect("Parses fine.");
+        let mut keys = prog
+            .transitions
+            .iter()
+            .map(|((state, read), _)| quote_key(state, read))
+            .collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec![
+                r#"("carry 0", "_")"#,
+                r#"("carry 1", "_")"#,
+                r#"("read", "0")"#,
+                r#"("read", "1")"#,
+            ]
+        );
+        let prog = prog.into_single_track().expect("Single track.");
+        let init = prog.initial().clone();
+        assert_eq!(
+            prog.respond(&init, &SmolStr::from("1")).goto,
+            Goto::Run(SmolStr::from("carry 1"))
+        );
+        assert_eq!(
+            prog.respond(&SmolStr::from("carry 1"), &SmolStr::from("_"))
+                .write,
+            "1"
+        );
+
+        let code = r#"Program(
+    initial: "read",
+    classes: {"bits": ["0", "1"]},
+    parameters: {"b": "bits"},
+    transitions: {
+        ("read", "_"): ("carry {b}", "_", Right),
+    },
+)"#;
+        let err = ProgramFile::parse(code.as_bytes()).expect_err("Unbound parameter.");
+        assert!(err.to_string().contains("not in its key"));
+
+        // A value that looks like a placeholder isn't substituted again.
+        assert_eq!(
+            substitute("{a} {b}", &[("{a}", "{b}"), ("{b}", "x")]),
+            "{b} x"
+        );
+    }
+
+    #[test]
+    fn syntaxes() {
+        let ron = ProgramFile::load(Path::new("examples/flip.ron")).expect("Example is valid.");
+        let json = r#"{
+    "initial": "flip",
+    "blank": "_",
+    "transitions": [
+        {"state": "flip", "read": "a", "goto": "flip", "write": "b", "move": "Right"},
+        {"state": "flip", "read": "b", "goto": "flip", "write": "a", "move": "Right"},
+        {"state": "flip", "read": "_", "goto": "accept", "write": "_", "move": "Stay"}
+    ]
+}"#;
+        let yaml = r#"
+initial: flip
+blank: _
+transitions:
+  - {state: flip, read: a, goto: flip, write: b, move: Right}
+  - {state: flip, read: b, goto: flip, write: a, move: Right}
+  - {state: flip, read: _, goto: accept, write: _, move: Stay}
+"#;
+        let toml = r#"
+initial = "flip"
+blank = "_"
+
+[[transitions]]
+state = "flip"
+read = "a"
+goto = "flip"
+write = "b"
+move = "Right"
+
+[[transitions]]
+state = "flip"
+read = "b"
+goto = "flip"
+write = "a"
+move = "Right"
+
+[[transitions]]
+state = "flip"
+read = "_"
+goto = "accept"
+write = "_"
+move = "Stay"
+"#;
+        for (syntax, code) in &[
+            (Syntax::Json, json),
+            (Syntax::Yaml, yaml),
+            (Syntax::Toml, toml),
+        ] {
+            let prog = ProgramFile::parse_as(code.as_bytes(), *syntax).expect("Valid program.");
+            assert_eq!(prog.rules(), ron.rules(), "{:?}", syntax);
+        }
+
+        // Duplicates are caught the same way, at the transition that repeats a key.
+        let json = json.replace(r#""read": "b""#, r#""read": "a""#);
+        let err = ProgramFile::parse_as(json.as_bytes(), Syntax::Json).expect_err("Duplicate key.");
+        assert!(
+            err.to_string().starts_with("Duplicate transition:"),
+            "{}",
+            err
+        );
+        assert!(err.to_string().contains("line 6"), "{}", err);
+        let yaml = yaml.replace("read: b", "read: a");
+        let err = ProgramFile::parse_as(yaml.as_bytes(), Syntax::Yaml).expect_err("Duplicate key.");
+        assert!(err.to_string().contains("line 6"), "{}", err);
+        let err = ProgramFile::parse_as("{\"blank\": \"_\"}".as_bytes(), Syntax::Json)
+            .expect_err("No transitions.");
+        assert!(err.to_string().contains("line 1"), "{}", err);
+    }
+
+    #[test]
+    fn includes() {
+        let prog =
+            ProgramFile::load(Path::new("examples/increment.ron")).expect("Example is valid.");
+        assert_eq!(prog.validate(), vec![]);
+        let prog = prog.into_single_track().expect("Single track.");
+        let init = prog.initial().clone();
+        assert_eq!(init, "end/scan");
+        assert_eq!(
+            prog.respond(&init, &SmolStr::from("_")).goto,
+            Goto::Run(SmolStr::from("carry"))
+        );
+        assert_eq!(
+            prog.respond(&init, &SmolStr::from("1")).goto,
+            Goto::Run(SmolStr::from("end/scan"))
+        );
+
+        // Declaring states covers the ones the included machine mentions.
+        let code = r#"Program(
+    initial: "main",
+    blank: "_",
+    states: ["main"],
+    wildcard: "*",
+    include: {
+        "end": (file: "parts/right_end.ron", accept: "back"),
+    },
+    transitions: {
+        ("main", "*"): ("end", "*", Stay),
+    },
+)"#;
+        let prog = ProgramFile::parse_in(
+            code.as_bytes(),
+            Syntax::Ron,
+            Path::new("examples"),
+            &mut vec![],
+        )
+        .expect("Includes the part.");
+        let messages = prog
+            .validate()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                r#"warning: ("end/scan", "_"): goes to "back", which has no transitions and so rejects."#
+            ]
+        );
+    }
+
+    #[test]
+    fn rules() {
+        let prog = ProgramFile::load(Path::new("examples/w#w.ron")).expect("Example is valid.");
+        let rules = prog.rules().expect("Single track.");
+        assert_eq!(rules[0].state, "read");
+        let first = |state: &str, read: &str| {
+            rules
+                .iter()
+                .find(|rule| rule.state == state && rule.read == read)
+                .cloned()
+        };
+        assert_eq!(
+            first("search 1 left", "0"),
+            Some(Rule {
+                state: "search 1 left".into(),
+                read: "0".into(),
+                goto: "search 1 left".into(),
+                write: "0".into(),
+                mv: Some(program::Movement::Right),
+            })
+        );
+        // The wildcard covers every symbol without a transition of its own.
+        assert_eq!(
+            first("end", "#").map(|rule| rule.goto),
+            Some("reject".into())
+        );
+        assert_eq!(first("end", "x").map(|rule| rule.goto), Some("end".into()));
+    }
+
+    #[test]
+    fn ron_round_trip() {
+        let mut prog = ProgramFile::new("start", "_");
+        prog.set_halting(vec!["done".into()], vec![]);
+        let rule = Rule {
+            state: "start".into(),
+            read: "\"".into(),
+            goto: "done".into(),
+            write: "\\".into(),
+            mv: None,
+        };
+        prog.add_rule(rule.clone()).expect("First transition.");
+        assert!(prog.add_rule(rule.clone()).is_err());
+
+        let code = prog.to_ron();
+        let parsed = ProgramFile::parse(code.as_bytes()).expect("Writes valid RON.");
+        assert_eq!(parsed.accept_states(), &["done".to_string()]);
+        assert_eq!(parsed.rules().expect("Single track."), vec![rule]);
+        assert_eq!(parsed.to_ron(), code);
+    }
+
+    #[test]
+    fn yaml_round_trip() {
+        for path in &["examples/w#w.ron", "examples/hello.ron"] {
+            let prog = ProgramFile::load(Path::new(path)).expect("Example is valid.");
+            let code = prog.to_yaml();
+            let parsed = ProgramFile::parse_as(code.as_bytes(), Syntax::Yaml)
+                .unwrap_or_else(|e| panic!("Writes valid YAML for {}: {}\n{}", path, e, code));
+            assert_eq!(parsed.to_ron(), prog.to_ron());
+        }
+    }
+
+    #[test]
+    fn automata_in_other_syntaxes() {
+        let json = r#"{
+    "kind": "Dfa",
+    "initial": "even",
+    "accept": ["even"],
+    "transitions": [
+        {"state": "even", "read": "0", "goto": "odd"},
+        {"state": "odd", "read": "0", "goto": "even"}
+    ]
+}"#;
+        assert_eq!(
+            read_kind(json, Syntax::Json).expect("Valid DFA."),
+            Kind::Dfa
+        );
+        let dfa = read_dfa(json, Syntax::Json).expect("Valid DFA.");
+        assert!(dfa.accepts(vec![SmolStr::from("0"), SmolStr::from("0")]));
+
+        let yaml = r#"
+kind: Pda
+initial: start
+accept: [done]
+transitions:
+  - {state: start, read: "", pop: "", moves: [[open, [$]]]}
+  - {state: open, read: "(", pop: "", moves: [[open, ["("]]]}
+  - {state: open, read: ")", pop: "(", moves: [[open, []]]}
+  - {state: open, read: "", pop: $, moves: [[done, []]]}
+"#;
+        assert_eq!(
+            read_kind(yaml, Syntax::Yaml).expect("Valid PDA."),
+            Kind::Pda
+        );
+        let pda = read_pda(yaml, Syntax::Yaml).expect("Valid PDA.");
+        let input = |s: &str| {
+            s.chars()
+                .map(|c| SmolStr::from(c.to_string()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(pda.accepts(input("(())"), 1000), Some(true));
+        assert_eq!(pda.accepts(input("(()"), 1000), Some(false));
+
+        let json = json.replace(
+            r#""read": "0", "goto": "even""#,
+            r#""read": "0", "goto": "odd""#,
+        );
+        let json = json.replace(r#""state": "odd""#, r#""state": "even""#);
+        let err = read_dfa(&json, Syntax::Json).expect_err("Duplicate key.");
+        assert!(
+            err.to_string().starts_with("Duplicate transition"),
+            "{}",
+            err
         );
     }
 }
@@ -0,0 +1,344 @@
+use std::{collections::HashMap, error::Error, fmt, fmt::Debug, hash::Hash};
+
+use crate::{
+    program::{Goto, Movement, ProgramBuilder, Response},
+    tape::Tape,
+};
+
+/// A transition, as `(state, read, response)`.
+pub type Transition<State, Alphabet> = (State, Alphabet, Response<State, Alphabet>);
+
+/// Two transitions that can lead into the same configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict<State, Alphabet> {
+    pub first: Transition<State, Alphabet>,
+    pub second: Transition<State, Alphabet>,
+}
+
+/// The reversal couldn't get back to the initial configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stuck<State, Alphabet> {
+    /// No transition leads to this configuration.
+    Unreachable { state: Goto<State>, read: Alphabet },
+    /// Still short of the initial configuration after this many steps.
+    OutOfSteps(u64),
+}
+
+/// The state a transition leaves the machine in. Emitting a word keeps running in the
+/// state it names, so it enters that state just like `Run` does.
+fn entered<State: Clone>(goto: &Goto<State>) -> Goto<State> {
+    match goto {
+        Goto::Run(state) | Goto::Emit(state) => Goto::Run(state.clone()),
+        Goto::Halt(accept) => Goto::Halt(*accept),
+    }
+}
+
+/// Checks that every configuration has at most one predecessor.
+/// Two transitions entering the same state must move the same way and write different symbols;
+/// otherwise the symbol left behind can't tell which of them was taken.
+pub fn check<State, Alphabet>(
+    prog: &ProgramBuilder<State, Alphabet>,
+) -> Result<(), Conflict<State, Alphabet>>
+where
+    State: Hash + Eq + Clone,
+    Alphabet: Hash + Eq + Clone,
+{
+    let mut by_goto: HashMap<Goto<State>, Vec<(&State, &Alphabet, &Response<_, _>)>> =
+        HashMap::new();
+    for (state, read, response) in prog.transitions() {
+        by_goto
+            .entry(entered(&response.goto))
+            .or_default()
+            .push((state, read, response));
+    }
+    for entering in by_goto.values() {
+        for (i, first) in entering.iter().enumerate() {
+            for second in &entering[i + 1..] {
+                if first.2.mv != second.2.mv || first.2.write == second.2.write {
+                    let owned = |(state, read, response): &(&State, &Alphabet, &Response<_, _>)| {
+                        ((*state).clone(), (*read).clone(), (*response).clone())
+                    };
+                    return Err(Conflict {
+                        first: owned(first),
+                        second: owned(second),
+                    });
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs a reversible machine backwards, undoing one transition per step.
+#[derive(Debug)]
+pub struct Reverser<State, Alphabet, TapeImpl>
+where
+    State: Hash + Eq,
+{
+    state: Goto<State>,
+    initial: State,
+    inverse: HashMap<Goto<State>, Vec<Transition<State, Alphabet>>>,
+    tape: TapeImpl,
+    /// How far right of the input's first cell the R/W head is.
+    head: isize,
+}
+
+impl<State, Alphabet, TapeImpl> Reverser<State, Alphabet, TapeImpl>
+where
+    State: Hash + Eq + Clone,
+    Alphabet: Hash + Eq + Clone,
+    TapeImpl: Tape<Alphabet>,
+{
+    /// Starts from the configuration the machine halted in. The tape's R/W head is over the
+    /// input's first cell, and the machine stopped `head` cells to its right.
+    /// Checks the program is reversible first.
+    pub fn new(
+        prog: &ProgramBuilder<State, Alphabet>,
+        initial: State,
+        halted: Goto<State>,
+        mut tape: TapeImpl,
+        head: usize,
+    ) -> Result<Self, Conflict<State, Alphabet>> {
+        check(prog)?;
+        let mut inverse: HashMap<_, Vec<_>> = HashMap::new();
+        for (state, read, response) in prog.transitions() {
+            inverse.entry(entered(&response.goto)).or_default().push((
+                state.clone(),
+                read.clone(),
+                response.clone(),
+            ));
+        }
+        for _ in 0..head {
+            tape.move_right();
+        }
+        Ok(Self {
+            state: halted,
+            initial,
+            inverse,
+            tape,
+            head: head as isize,
+        })
+    }
+
+    /// Undoes transitions until the machine is back in its initial state with the R/W head over
+    /// the input's first cell, taking at most `limit` steps.
+    pub fn run(&mut self, limit: u64) -> Result<(), Stuck<State, Alphabet>> {
+        let mut steps = 0;
+        while !self.at_start() {
+            if steps == limit {
+                return Err(Stuck::OutOfSteps(limit));
+            }
+            if !self.step() {
+                return Err(Stuck::Unreachable {
+                    state: self.state.clone(),
+                    read: self.tape.get().clone(),
+                });
+            }
+            steps += 1;
+        }
+        Ok(())
+    }
+
+    fn at_start(&self) -> bool {
+        self.head == 0 && matches!(&self.state, Goto::Run(state) if *state == self.initial)
+    }
+
+    /// Undoes one transition, returning false if no transition leads to this configuration.
+    pub fn step(&mut self) -> bool {
+        let candidates = match self.inverse.get(&self.state) {
+            Some(candidates) => candidates,
+            None => return false,
+        };
+        for (state, read, response) in candidates {
+            if let Some(mv) = &response.mv {
+                self.tape.move_head(mv.opposite());
+            }
+            if *self.tape.get() == response.write {
+                *self.tape.get_mut() = read.clone();
+                self.state = Goto::Run(state.clone());
+                self.head -= offset(&response.mv);
+                return true;
+            }
+            if let Some(mv) = &response.mv {
+                self.tape.move_head(mv.clone());
+            }
+        }
+        false
+    }
+
+    pub fn get_tape(self) -> impl Iterator<Item = Alphabet> {
+        self.tape.get_all()
+    }
+}
+
+fn offset(mv: &Option<Movement>) -> isize {
+    match mv {
+        Some(Movement::Left) => -1,
+        Some(Movement::Right) => 1,
+        None => 0,
+    }
+}
+
+fn describe_move(mv: &Option<Movement>) -> &'static str {
+    match mv {
+        Some(Movement::Left) => "Left",
+        Some(Movement::Right) => "Right",
+        None => "Stay",
+    }
+}
+
+impl<State, Alphabet> fmt::Display for Conflict<State, Alphabet>
+where
+    State: Debug,
+    Alphabet: Debug + PartialEq,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (s1, r1, first) = &self.first;
+        let (s2, r2, second) = &self.second;
+        write!(
+            f,
+            "Not reversible: ({:?}, {:?}) and ({:?}, {:?}) both enter {:?}",
+            s1, r1, s2, r2, first.goto
+        )?;
+        if first.mv != second.mv {
+            write!(
+                f,
+                " but move differently ({} and {}).",
+                describe_move(&first.mv),
+                describe_move(&second.mv)
+            )
+        } else {
+            write!(f, " writing {:?} and moving the same way.", first.write)
+        }
+    }
+}
+
+impl<State, Alphabet> Error for Conflict<State, Alphabet>
+where
+    State: Debug,
+    Alphabet: Debug + PartialEq,
+{
+}
+
+impl<State, Alphabet> fmt::Display for Stuck<State, Alphabet>
+where
+    State: Debug,
+    Alphabet: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stuck::Unreachable { state, read } => write!(
+                f,
+                "No transition leads to {:?} reading {:?}, so this configuration is unreachable.",
+                state, read
+            ),
+            Stuck::OutOfSteps(limit) => write!(
+                f,
+                "Still not back at the initial configuration after {} steps.",
+                limit
+            ),
+        }
+    }
+}
+
+impl<State, Alphabet> Error for Stuck<State, Alphabet>
+where
+    State: Debug,
+    Alphabet: Debug,
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{program::Movement::*, tape::Unbounded, turing_machine::TuringMachine};
+
+    // Flips every bit up to the first blank, then accepts.
+    fn flipper() -> ProgramBuilder<&'static str, u8> {
+        ProgramBuilder::new()
+            .with_transition(("flip", 1), (Goto::Run("flip"), 2, Some(Right)))
+            .with_transition(("flip", 2), (Goto::Run("flip"), 1, Some(Right)))
+            .with_transition(("flip", 0), (Goto::Halt(true), 0, None))
+    }
+
+    #[test]
+    fn reversible() {
+        assert_eq!(check(&flipper()), Ok(()));
+    }
+
+    #[test]
+    fn same_write() {
+        let prog = flipper().with_transition(("other", 0), (Goto::Run("flip"), 1, Some(Right)));
+        assert!(check(&prog).is_err());
+    }
+
+    #[test]
+    fn different_moves() {
+        let prog = ProgramBuilder::new()
+            .with_transition(("a", 0), (Goto::Run("b"), 0, Some(Left)))
+            .with_transition(("a", 1), (Goto::Run("b"), 1, Some(Right)));
+        let conflict = check(&prog).expect_err("Moves differ.");
+        assert!(conflict.to_string().contains("move differently"));
+    }
+
+    #[test]
+    fn round_trip() {
+        let input = vec![1, 2, 2, 1];
+        let mut m = TuringMachine::new(flipper().build("flip"), Unbounded::from(input.clone()));
+        assert!(m.run());
+        let output = m.get_tape().collect::<Vec<_>>();
+        assert_eq!(output, vec![2, 1, 1, 2, 0]);
+
+        let tape = Unbounded::from(output);
+        let mut rev =
+            Reverser::new(&flipper(), "flip", Goto::Halt(true), tape, 4).expect("Reversible.");
+        rev.run(100).expect("Reachable configuration.");
+        assert_eq!(rev.get_tape().collect::<Vec<_>>(), vec![1, 2, 2, 1, 0]);
+    }
+
+    #[test]
+    fn stops_at_the_initial_configuration() {
+        // "start" loops on blanks without moving, so it always has a predecessor.
+        let prog = ProgramBuilder::new()
+            .with_transition(("start", 0), (Goto::Run("start"), 0, None))
+            .with_transition(("start", 1), (Goto::Run("end"), 2, Some(Right)))
+            .with_transition(("end", 0), (Goto::Halt(true), 0, None));
+        let tape = Unbounded::from(vec![2, 0]);
+        let mut rev =
+            Reverser::new(&prog, "start", Goto::Halt(true), tape, 1).expect("Reversible.");
+        rev.run(100).expect("Reachable configuration.");
+        assert_eq!(rev.get_tape().collect::<Vec<_>>(), vec![1, 0]);
+
+        // Without a way back to the first cell, the limit ends it.
+        let tape = Unbounded::from(vec![0]);
+        let mut rev =
+            Reverser::new(&prog, "end", Goto::Run("start"), tape, 0).expect("Reversible.");
+        assert_eq!(rev.run(5), Err(Stuck::OutOfSteps(5)));
+    }
+
+    #[test]
+    fn leading_blanks_count_towards_the_head() {
+        // Erasing the first cell leaves a blank that a trimmed tape would drop.
+        let prog = ProgramBuilder::new()
+            .with_transition(("erase", 1), (Goto::Run("skip"), 0, Some(Right)))
+            .with_transition(("skip", 2), (Goto::Halt(true), 2, None));
+        let tape = Unbounded::from(vec![0, 2]);
+        let mut rev =
+            Reverser::new(&prog, "erase", Goto::Halt(true), tape, 1).expect("Reversible.");
+        rev.run(100).expect("Reachable configuration.");
+        assert_eq!(rev.get_tape().collect::<Vec<_>>(), vec![1, 2]);
+
+        let tape = Unbounded::from(vec![2]);
+        let mut rev =
+            Reverser::new(&prog, "erase", Goto::Halt(true), tape, 0).expect("Reversible.");
+        assert!(rev.run(100).is_err());
+    }
+
+    #[test]
+    fn emitting_enters_the_state_too() {
+        let prog = ProgramBuilder::new()
+            .with_transition(("a", 0), (Goto::Run("b"), 1, Some(Right)))
+            .with_transition(("a", 1), (Goto::Emit("b"), 1, Some(Right)));
+        assert!(check(&prog).is_err());
+    }
+}
@@ -0,0 +1,287 @@
+use std::{error::Error, fmt};
+
+use crate::{
+    program::Movement,
+    program_ron::{ProgramFile, Rule},
+};
+
+/// The column header standing for the blank symbol.
+pub const BLANK: &str = "_";
+
+const ACCEPT: &str = "accept";
+const REJECT: &str = "reject";
+
+/// A CSV table we couldn't read, with its position, or a program a table can't express.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpreadsheetError(String);
+
+/// Reads a transition table with a row per state and a column per symbol. The header row
+/// names the symbols after a first cell that's ignored, with `_` for the blank, and the first
+/// state is the initial one. Each cell holds `write,move,next`, moving `L`, `R` or `S`;
+/// an empty cell rejects. Going to `accept` or `reject` halts.
+pub fn read(csv: &str) -> Result<ProgramFile, SpreadsheetError> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(csv.as_bytes());
+    let mut records = rdr.records();
+    let header = match records.next() {
+        Some(header) => header?,
+        None => return Err(SpreadsheetError("The table is empty.".into())),
+    };
+    let symbols = header.iter().skip(1).map(str::trim).collect::<Vec<_>>();
+    for (i, symbol) in symbols.iter().enumerate() {
+        let at = Position {
+            row: 1,
+            column: i + 2,
+        };
+        if symbol.is_empty() {
+            return Err(at.error("The symbol is missing.".into()));
+        }
+        if symbols[..i].contains(symbol) {
+            return Err(at.error(format!("Symbol {:?} has two columns.", symbol)));
+        }
+    }
+
+    let mut prog = None;
+    let mut states = Vec::new();
+    for (i, record) in records.enumerate() {
+        let record = record?;
+        // Blank lines are skipped, so count rows as the file's lines.
+        let row = record.position().map_or(i + 2, |pos| pos.line() as usize);
+        let state = record.get(0).unwrap_or_default().trim();
+        if state.is_empty() {
+            if record.iter().all(|cell| cell.trim().is_empty()) {
+                continue;
+            }
+            return Err(Position { row, column: 1 }.error("The state is missing.".into()));
+        }
+        if states.contains(&state.to_owned()) {
+            return Err(
+                Position { row, column: 1 }.error(format!("State {:?} has two rows.", state))
+            );
+        }
+        states.push(state.to_owned());
+        let prog = prog.get_or_insert_with(|| ProgramFile::new(state, BLANK));
+
+        for (j, cell) in record.iter().enumerate().skip(1) {
+            let at = Position { row, column: j + 1 };
+            let cell = cell.trim();
+            if cell.is_empty() {
+                continue;
+            }
+            let read = symbols
+                .get(j - 1)
+                .ok_or_else(|| at.error("The cell is past the last symbol's column.".into()))?;
+            let (write, mv, goto) = match cell.split(',').map(str::trim).collect::<Vec<_>>()[..] {
+                [write, mv, goto] if !write.is_empty() && !goto.is_empty() => (write, mv, goto),
+                _ => return Err(at.error(format!("Expected `write,move,next`, found {:?}.", cell))),
+            };
+            let mv = match mv {
+                "L" | "l" => Some(Movement::Left),
+                "R" | "r" => Some(Movement::Right),
+                "S" | "s" => None,
+                other => {
+                    return Err(at.error(format!("Unknown move {:?}, expected L, R or S.", other)))
+                }
+            };
+            prog.add_rule(Rule {
+                state: state.to_owned(),
+                read: read.to_string(),
+                goto: goto.to_owned(),
+                write: write.to_owned(),
+                mv,
+            })
+            .map_err(|e| at.error(e.to_string()))?;
+        }
+    }
+    prog.ok_or_else(|| SpreadsheetError("The table has no states.".into()))
+}
+
+/// Writes a program as a transition table. States come in the order the program mentions
+/// them, halting states become `accept` and `reject`, and the blank's column is `_`.
+pub fn write(prog: &ProgramFile) -> Result<String, SpreadsheetError> {
+    let rules = prog.rules()?;
+    let blank = prog.blank();
+    let accepts = |state: &str| prog.accept_states().iter().any(|s| s == state);
+    let rejects = |state: &str| prog.reject_states().iter().any(|s| s == state);
+
+    let symbol = |symbol: &str| -> Result<String, SpreadsheetError> {
+        if symbol == blank {
+            Ok(BLANK.into())
+        } else if symbol == BLANK || symbol.is_empty() || symbol.contains(',') {
+            Err(SpreadsheetError(format!(
+                "Symbol {:?} can't be written in a table.",
+                symbol
+            )))
+        } else {
+            Ok(symbol.to_owned())
+        }
+    };
+    let state_name = |state: &str| -> Result<String, SpreadsheetError> {
+        if accepts(state) {
+            Ok(ACCEPT.into())
+        } else if rejects(state) {
+            Ok(REJECT.into())
+        } else if state == ACCEPT || state == REJECT || state.contains(',') || state.is_empty() {
+            Err(SpreadsheetError(format!(
+                "State {:?} can't be written in a table.",
+                state
+            )))
+        } else {
+            Ok(state.to_owned())
+        }
+    };
+
+    let mut states: Vec<&str> = vec![prog.initial()];
+    let mut symbols = vec![BLANK.to_owned()];
+    for rule in &rules {
+        if !states.contains(&rule.state.as_str()) {
+            states.push(&rule.state);
+        }
+        let read = symbol(&rule.read)?;
+        if !symbols.contains(&read) {
+            symbols.push(read);
+        }
+    }
+    symbols[1..].sort();
+
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+    let header = Some("state".to_owned()).into_iter().chain(symbols.clone());
+    wtr.write_record(header)?;
+    for state in states {
+        let mut record = vec![state_name(state)?];
+        for read in &symbols {
+            let rule = rules
+                .iter()
+                .find(|rule| rule.state == state && symbol(&rule.read).as_ref() == Ok(read));
+            let cell = match rule {
+                None => String::new(),
+                Some(rule) => {
+                    let mv = match rule.mv {
+                        Some(Movement::Left) => "L",
+                        Some(Movement::Right) => "R",
+                        None => "S",
+                    };
+                    format!(
+                        "{},{},{}",
+                        symbol(&rule.write)?,
+                        mv,
+                        state_name(&rule.goto)?
+                    )
+                }
+            };
+            record.push(cell);
+        }
+        wtr.write_record(record)?;
+    }
+    let bytes = wtr
+        .into_inner()
+        .map_err(|e| SpreadsheetError(e.to_string()))?;
+    String::from_utf8(bytes).map_err(|e| SpreadsheetError(e.to_string()))
+}
+
+/// A cell, counting rows and columns from 1 like a spreadsheet does.
+struct Position {
+    row: usize,
+    column: usize,
+}
+
+impl Position {
+    fn error(&self, message: String) -> SpreadsheetError {
+        SpreadsheetError(format!(
+            "Row {}, column {}: {}",
+            self.row, self.column, message
+        ))
+    }
+}
+
+impl fmt::Display for SpreadsheetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for SpreadsheetError {}
+
+impl From<csv::Error> for SpreadsheetError {
+    fn from(e: csv::Error) -> Self {
+        SpreadsheetError(format!("Malformed CSV: {}", e))
+    }
+}
+
+impl From<ron::de::Error> for SpreadsheetError {
+    fn from(e: ron::de::Error) -> Self {
+        SpreadsheetError(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FLIP: &str = "\
+state,_,a,b
+flip,\"_,S,accept\",\"b,R,flip\",\"a,R,flip\"
+";
+
+    #[test]
+    fn import() {
+        let prog = read(FLIP).expect("Valid table.");
+        assert_eq!(prog.initial(), "flip");
+        assert_eq!(
+            prog.rules().expect("Single track."),
+            vec![
+                Rule {
+                    state: "flip".into(),
+                    read: "_".into(),
+                    goto: "accept".into(),
+                    write: "_".into(),
+                    mv: None,
+                },
+                Rule {
+                    state: "flip".into(),
+                    read: "a".into(),
+                    goto: "flip".into(),
+                    write: "b".into(),
+                    mv: Some(Movement::Right),
+                },
+                Rule {
+                    state: "flip".into(),
+                    read: "b".into(),
+                    goto: "flip".into(),
+                    write: "a".into(),
+                    mv: Some(Movement::Right),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let prog = read(FLIP).expect("Valid table.");
+        assert_eq!(write(&prog).as_deref(), Ok(FLIP));
+    }
+
+    #[test]
+    fn empty_cells_reject() {
+        let prog = read("state,_,a\nq0,,\"a,R,q1\"\nq1,\"_,S,accept\",\n").expect("Valid table.");
+        let rules = prog.rules().expect("Single track.");
+        assert_eq!(rules.len(), 2);
+        assert!(!rules
+            .iter()
+            .any(|rule| rule.state == "q1" && rule.read == "a"));
+    }
+
+    #[test]
+    fn errors_give_positions() {
+        let err = read("state,_,a\nq0,\"_,S,accept\",\"a,X,q0\"\n").unwrap_err();
+        assert!(err.to_string().starts_with("Row 2, column 3:"), "{}", err);
+        let err = read("state,_,a\nq0,,\n\nq1,,\nq0,,\n").unwrap_err();
+        assert!(err.to_string().starts_with("Row 5, column 1:"), "{}", err);
+        let err = read("state,_,_\n").unwrap_err();
+        assert!(err.to_string().starts_with("Row 1, column 3:"), "{}", err);
+        let err = read("state,_\nq0,,\"a,R,q0\"\n").unwrap_err();
+        assert!(err.to_string().starts_with("Row 2, column 3:"), "{}", err);
+    }
+}
@@ -1,7 +1,12 @@
 // Possible TODO: turn this into a multi-file module.
 
 use crate::program::Movement;
-use std::{borrow::Cow, collections::VecDeque, convert::TryFrom, iter::FromIterator};
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    convert::TryFrom,
+    iter::{repeat_n, FromIterator, Fuse},
+};
 
 pub trait Tape<Alphabet>
 where
@@ -37,6 +42,7 @@ where
 pub struct Unbounded<Alphabet> {
     idx: usize,
     tape: VecDeque<Alphabet>,
+    blank: Alphabet,
 }
 
 impl<Alphabet> Unbounded<Alphabet>
@@ -48,20 +54,54 @@ where
     }
 }
 
+impl<Alphabet> Unbounded<Alphabet>
+where
+    Alphabet: Clone,
+{
+    /// An empty tape that is filled with `blank` instead of the default symbol.
+    pub fn with_blank(blank: Alphabet) -> Self {
+        Self {
+            idx: 0,
+            tape: VecDeque::from(vec![blank.clone()]),
+            blank,
+        }
+    }
+
+    /// A tape holding `input`, with the R/W head over its first symbol.
+    pub fn from_input<I>(input: I, blank: Alphabet) -> Self
+    where
+        I: IntoIterator<Item = Alphabet>,
+    {
+        let tape = input.into_iter().collect::<VecDeque<_>>();
+        if tape.is_empty() {
+            return Self::with_blank(blank);
+        }
+        Self {
+            idx: 0,
+            tape,
+            blank,
+        }
+    }
+
+    pub fn blank(&self) -> &Alphabet {
+        &self.blank
+    }
+}
+
 impl<Alphabet> Tape<Alphabet> for Unbounded<Alphabet>
 where
-    Alphabet: Clone + Default + 'static,
+    Alphabet: Clone + 'static,
 {
     fn move_left(&mut self) {
         match self.idx.checked_sub(1) {
             Some(new_idx) => self.idx = new_idx,
-            None => self.tape.push_front(Default::default()),
+            None => self.tape.push_front(self.blank.clone()),
         }
     }
     fn move_right(&mut self) {
         self.idx += 1;
         if self.tape.get(self.idx).is_none() {
-            self.tape.push_back(Default::default());
+            self.tape.push_back(self.blank.clone());
         }
     }
 
@@ -86,8 +126,8 @@ where
             Box::new((-r..=r).map(move |i| {
                 usize::try_from(self.idx as isize + i)
                     .ok()
-                    .and_then(|index| self.tape.get(index).map(Cow::Borrowed))
-                    .unwrap_or(Cow::Owned(Default::default()))
+                    .and_then(|index| self.tape.get(index))
+                    .map_or(Cow::Borrowed(&self.blank), Cow::Borrowed)
             })),
         )
     }
@@ -99,27 +139,20 @@ where
 
 impl<T, Alphabet> From<T> for Unbounded<Alphabet>
 where
-    Alphabet: Default,
+    Alphabet: Clone + Default,
     VecDeque<Alphabet>: From<T>,
 {
     fn from(src: T) -> Self {
-        let mut ret = Self {
-            tape: VecDeque::from(src),
-            idx: 0,
-        };
-        if ret.tape.is_empty() {
-            ret = Self::new();
-        }
-        ret
+        Self::from_input(VecDeque::from(src), Default::default())
     }
 }
 
 impl<Alphabet> FromIterator<Alphabet> for Unbounded<Alphabet>
 where
-    Alphabet: Default,
+    Alphabet: Clone + Default,
 {
     fn from_iter<T: IntoIterator<Item = Alphabet>>(iter: T) -> Self {
-        Self::from(iter.into_iter().collect::<Vec<_>>())
+        Self::from_input(iter, Default::default())
     }
 }
 
@@ -131,10 +164,136 @@ where
         Self {
             idx: 0,
             tape: VecDeque::from(vec![Default::default()]),
+            blank: Default::default(),
         }
     }
 }
 
+/// A tape whose input is pulled from an iterator on demand.
+/// A cell is only read from the source the first time the R/W head moves onto it,
+/// so the input never has to be held in memory all at once (or even end).
+#[derive(Debug)]
+pub struct Streamed<Alphabet, I> {
+    idx: usize,
+    tape: VecDeque<Alphabet>,
+    source: Fuse<I>,
+    blank: Alphabet,
+}
+
+impl<Alphabet, I> Streamed<Alphabet, I>
+where
+    Alphabet: Clone + Default,
+    I: Iterator<Item = Alphabet>,
+{
+    pub fn new<S>(source: S) -> Self
+    where
+        S: IntoIterator<Item = Alphabet, IntoIter = I>,
+    {
+        Self::with_blank(source, Default::default())
+    }
+}
+
+impl<Alphabet, I> Streamed<Alphabet, I>
+where
+    Alphabet: Clone,
+    I: Iterator<Item = Alphabet>,
+{
+    pub fn with_blank<S>(source: S, blank: Alphabet) -> Self
+    where
+        S: IntoIterator<Item = Alphabet, IntoIter = I>,
+    {
+        let mut source = source.into_iter().fuse();
+        let first = source.next().unwrap_or_else(|| blank.clone());
+        Self {
+            idx: 0,
+            tape: VecDeque::from(vec![first]),
+            source,
+            blank,
+        }
+    }
+
+    pub fn blank(&self) -> &Alphabet {
+        &self.blank
+    }
+}
+
+impl<Alphabet, I> Tape<Alphabet> for Streamed<Alphabet, I>
+where
+    Alphabet: Clone + 'static,
+    I: Iterator<Item = Alphabet> + 'static,
+{
+    fn move_left(&mut self) {
+        match self.idx.checked_sub(1) {
+            Some(new_idx) => self.idx = new_idx,
+            None => self.tape.push_front(self.blank.clone()),
+        }
+    }
+    fn move_right(&mut self) {
+        self.idx += 1;
+        if self.tape.get(self.idx).is_none() {
+            let next = self.source.next().unwrap_or_else(|| self.blank.clone());
+            self.tape.push_back(next);
+        }
+    }
+
+    fn get(&self) -> &Alphabet {
+        self.tape
+            .get(self.idx)
+            .expect("Streamed tape must have R/W head over initialised cell.")
+    }
+    fn get_mut(&mut self) -> &mut Alphabet {
+        self.tape
+            .get_mut(self.idx)
+            .expect("Streamed tape must have R/W head over initialised cell.")
+    }
+
+    /// Input that hasn't been pulled from the source yet shows up as blank.
+    fn get_radius(
+        &self,
+        radius: usize,
+    ) -> (usize, Box<dyn Iterator<Item = Cow<'_, Alphabet>> + '_>) {
+        let r = radius as isize;
+        (
+            radius,
+            Box::new((-r..=r).map(move |i| {
+                usize::try_from(self.idx as isize + i)
+                    .ok()
+                    .and_then(|index| self.tape.get(index))
+                    .map_or(Cow::Borrowed(&self.blank), Cow::Borrowed)
+            })),
+        )
+    }
+
+    /// The part of the input the machine never reached is passed through untouched, still
+    /// pulled from the source only as the contents are taken.
+    fn get_all(self) -> Box<dyn Iterator<Item = Alphabet>> {
+        Box::new(self.tape.into_iter().chain(self.source))
+    }
+}
+
+/// Drops the blanks at either end of a tape's contents.
+/// Blanks in the middle are held back until the next non-blank symbol, so this stays lazy.
+pub fn trim_blanks<I, Alphabet>(tape: I, blank: Alphabet) -> impl Iterator<Item = Alphabet>
+where
+    I: IntoIterator<Item = Alphabet>,
+    Alphabet: Clone + PartialEq,
+{
+    let mut held = 0;
+    let mut started = false;
+    tape.into_iter().flat_map(move |symbol| {
+        let mut out = Vec::new();
+        if symbol == blank {
+            held += started as usize;
+        } else {
+            started = true;
+            out.extend(repeat_n(blank.clone(), held));
+            out.push(symbol);
+            held = 0;
+        }
+        out
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -168,4 +327,62 @@ mod tests {
         tape.move_left();
         assert_eq!(tape.get(), &-900);
     }
+
+    #[test]
+    fn streamed_reads_lazily() {
+        use std::{cell::Cell, rc::Rc};
+
+        let pulled = Rc::new(Cell::new(0));
+        let counter = Rc::clone(&pulled);
+        let source = (1..=3).inspect(move |_| counter.set(counter.get() + 1));
+        let mut tape = Streamed::new(source);
+        assert_eq!(pulled.get(), 1);
+        assert_eq!(tape.get(), &1);
+        tape.move_left();
+        assert_eq!(tape.get(), &0);
+        tape.move_right();
+        assert_eq!(pulled.get(), 1);
+        tape.move_right();
+        assert_eq!(pulled.get(), 2);
+        assert_eq!(tape.get(), &2);
+        *tape.get_mut() = 7;
+        let mut all = tape.get_all();
+        assert_eq!(all.by_ref().take(3).collect::<Vec<_>>(), vec![0, 1, 7]);
+        assert_eq!(pulled.get(), 2);
+        assert_eq!(all.collect::<Vec<_>>(), vec![3]);
+        assert_eq!(pulled.get(), 3);
+    }
+
+    #[test]
+    fn streamed_empty_source() {
+        let mut tape = Streamed::new(Vec::<u8>::new());
+        assert_eq!(tape.get(), &0);
+        tape.move_right();
+        assert_eq!(tape.get(), &0);
+    }
+
+    #[test]
+    fn custom_blank() {
+        let mut tape = Unbounded::from_input(vec!['a'], '_');
+        tape.move_left();
+        assert_eq!(tape.get(), &'_');
+        tape.move_right();
+        tape.move_right();
+        assert_eq!(tape.get(), &'_');
+        let (head, cells) = tape.get_radius(1);
+        assert_eq!(head, 1);
+        assert_eq!(cells.map(Cow::into_owned).collect::<String>(), "a__");
+
+        let mut tape = Streamed::with_blank(Vec::new(), '_');
+        assert_eq!(tape.get(), &'_');
+        tape.move_right();
+        assert_eq!(tape.get(), &'_');
+    }
+
+    #[test]
+    fn trim() {
+        let trimmed = trim_blanks("__a_b__c___".chars(), '_').collect::<String>();
+        assert_eq!(trimmed, "a_b__c");
+        assert_eq!(trim_blanks("___".chars(), '_').count(), 0);
+    }
 }
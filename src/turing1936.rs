@@ -0,0 +1,371 @@
+use std::{error::Error, fmt};
+
+use crate::{
+    program::Movement,
+    program_ron::{ProgramFile, Rule},
+};
+
+/// The blank square, which the paper calls "None".
+pub const BLANK: &str = " ";
+
+/// The wildcard of the programs read from tables. No column can contain it, so it never
+/// clashes with a symbol.
+const WILDCARD: &str = "|";
+
+/// A table in the paper's notation we couldn't read, with its line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Turing1936Error(String);
+
+/// What a row of the table is conditioned on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Condition {
+    Symbol(String),
+    /// A blank square.
+    None,
+    /// Any symbol but the blank.
+    Any,
+    /// Whatever is scanned, when the symbol column is left empty.
+    Always,
+}
+
+/// A single operation of an instruction.
+enum Operation {
+    Print(String),
+    Erase,
+    Move(Movement),
+}
+
+/// One row of the table.
+struct Row {
+    line: usize,
+    m_config: String,
+    condition: Condition,
+    operations: Vec<Operation>,
+    goto: String,
+}
+
+/// Reads a table of m-configurations, one row per line written
+/// `m-config | symbol | operations | final m-config`, with `//` comments.
+///
+/// The symbol is a symbol, `None` for a blank square, `Any` for any other, or left empty
+/// for whatever is scanned. Operations are `P` with a symbol to print, `E` to erase, `R` and `L`,
+/// separated by commas. A row whose m-config is left empty continues the one above, and the
+/// first m-config is the initial one.
+///
+/// Each row becomes a chain of single-step transitions through states named after its
+/// m-config, like `b:None:1`, or `b:|:1` for an empty symbol, so traces point back to the
+/// row. M-configs can't contain `:`, so these never clash with them.
+///
+/// Rows for particular symbols take precedence over `Any`, and an empty symbol beside `Any`
+/// covers the blank. An m-config with an `Any` row but none for a blank square rejects on one.
+pub fn read(text: &str) -> Result<ProgramFile, Turing1936Error> {
+    let mut rows = Vec::<Row>::new();
+    for (i, line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let err = |message: String| Turing1936Error(format!("Line {}: {}", line_no, message));
+        let code = line.split("//").next().unwrap_or_default();
+        if code.trim().is_empty() {
+            continue;
+        }
+        let columns = code.split('|').map(str::trim).collect::<Vec<_>>();
+        let (m_config, symbol, operations, goto) = match columns[..] {
+            [m_config, symbol, operations, goto] => (m_config, symbol, operations, goto),
+            _ => {
+                return Err(err(format!(
+                    "Expected `m-config | symbol | operations | final m-config`, found {} columns.",
+                    columns.len()
+                )))
+            }
+        };
+        let m_config = match (m_config, rows.last()) {
+            ("", Some(previous)) => previous.m_config.clone(),
+            ("", None) => return Err(err("The first row needs an m-configuration.".into())),
+            (m_config, _) => m_config.to_owned(),
+        };
+        if goto.is_empty() {
+            return Err(err("The final m-configuration is missing.".into()));
+        }
+        if let Some(name) = [m_config.as_str(), goto]
+            .iter()
+            .find(|name| name.contains(':'))
+        {
+            return Err(err(format!(
+                "{:?} contains `:`, which is kept for the states a row steps through.",
+                name
+            )));
+        }
+        let condition = match symbol {
+            "" => Condition::Always,
+            "None" => Condition::None,
+            "Any" => Condition::Any,
+            symbol => Condition::Symbol(symbol.to_owned()),
+        };
+        let operations = operations
+            .split(',')
+            .map(str::trim)
+            .filter(|op| !op.is_empty())
+            .map(|op| match op {
+                "R" => Ok(Operation::Move(Movement::Right)),
+                "L" => Ok(Operation::Move(Movement::Left)),
+                "E" => Ok(Operation::Erase),
+                op if op.starts_with('P') && op.len() > 1 => {
+                    Ok(Operation::Print(op[1..].trim().to_owned()))
+                }
+                op => Err(err(format!(
+                    "Unknown operation {:?}, expected P followed by a symbol, E, R or L.",
+                    op
+                ))),
+            })
+            .collect::<Result<_, _>>()?;
+        if let Some(first) = rows
+            .iter()
+            .find(|row| row.m_config == m_config && row.condition == condition)
+        {
+            return Err(err(format!(
+                "{} already has a row for {:?}, on line {}.",
+                m_config, symbol, first.line
+            )));
+        }
+        rows.push(Row {
+            line: line_no,
+            m_config,
+            condition,
+            operations,
+            goto: goto.to_owned(),
+        });
+    }
+    let initial = match rows.first() {
+        Some(row) => row.m_config.clone(),
+        None => return Err(Turing1936Error("The table is empty.".into())),
+    };
+
+    let mut prog = ProgramFile::new(initial, BLANK);
+    prog.set_wildcard(WILDCARD);
+    let reject = prog.reject_states()[0].clone();
+    for row in &rows {
+        let err = |e: ron::de::Error| Turing1936Error(format!("Line {}: {}", row.line, e));
+        let has_row = |condition: Condition| {
+            rows.iter()
+                .any(|other| other.m_config == row.m_config && other.condition == condition)
+        };
+        let read = match &row.condition {
+            Condition::Symbol(symbol) => symbol.as_str(),
+            Condition::None => BLANK,
+            // With an `Any` row beside it, an empty symbol is left with the blank.
+            Condition::Always if has_row(Condition::Any) => BLANK,
+            // The rows for particular symbols take precedence over the wildcard.
+            Condition::Always | Condition::Any => WILDCARD,
+        };
+        if row.condition == Condition::Any
+            && !has_row(Condition::None)
+            && !has_row(Condition::Always)
+        {
+            // Any doesn't match a blank square, so the m-config has nothing to do there.
+            prog.add_rule(Rule {
+                state: row.m_config.clone(),
+                read: BLANK.to_owned(),
+                goto: reject.clone(),
+                write: BLANK.to_owned(),
+                mv: None,
+            })
+            .map_err(err)?;
+        }
+        let steps = steps(&row.operations);
+        let label = match &row.condition {
+            Condition::Symbol(symbol) => symbol.as_str(),
+            Condition::None => "None",
+            Condition::Any => "Any",
+            // No symbol can contain the wildcard, so this never clashes with one.
+            Condition::Always => WILDCARD,
+        };
+        let state = |k: usize| match k {
+            0 => row.m_config.clone(),
+            k if k == steps.len() => row.goto.clone(),
+            k => format!("{}:{}:{}", row.m_config, label, k),
+        };
+        for (k, (write, mv)) in steps.iter().enumerate() {
+            // Only the first step depends on the scanned symbol.
+            let read = if k == 0 { read } else { WILDCARD };
+            let write = match write {
+                Some(symbol) => symbol.as_str(),
+                None if read == WILDCARD => WILDCARD,
+                None => read,
+            };
+            prog.add_rule(Rule {
+                state: state(k),
+                read: read.to_owned(),
+                goto: state(k + 1),
+                write: write.to_owned(),
+                mv: mv.clone(),
+            })
+            .map_err(err)?;
+        }
+    }
+    Ok(prog)
+}
+
+/// Groups operations into steps of a write followed by a move, either of which may be missing.
+fn steps(operations: &[Operation]) -> Vec<(Option<String>, Option<Movement>)> {
+    let mut steps = Vec::new();
+    let mut pending = None;
+    for op in operations {
+        match op {
+            Operation::Print(_) | Operation::Erase => {
+                if let Some(write) = pending.take() {
+                    steps.push((Some(write), None));
+                }
+                pending = Some(match op {
+                    Operation::Print(symbol) => symbol.clone(),
+                    _ => BLANK.to_owned(),
+                });
+            }
+            Operation::Move(mv) => steps.push((pending.take(), Some(mv.clone()))),
+        }
+    }
+    if pending.is_some() || steps.is_empty() {
+        steps.push((pending, None));
+    }
+    steps
+}
+
+impl fmt::Display for Turing1936Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for Turing1936Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        program::{Goto, TransitionFn},
+        tape::{trim_blanks, Unbounded},
+        turing_machine::TuringMachine,
+    };
+    use smol_str::SmolStr;
+
+    // The paper's first example, printing 0 1 0 1 ... on alternate squares.
+    const ALTERNATE: &str = "
+b | None | P0, R | c
+c | None | R     | e
+e | None | P1, R | f
+f | None | R     | b
+";
+
+    // The same machine, in the paper's compact form.
+    const COMPACT: &str = "
+b | None | P0      | b   // start by printing 0
+  | 0    | R, R, P1 | b
+  | 1    | R, R, P0 | b
+";
+
+    fn tape_after(text: &str, steps: usize) -> String {
+        let prog = read(text).expect("Valid table.").into_single_track();
+        let prog = prog.expect("Single track.");
+        let mut machine = TuringMachine::new(prog, Unbounded::with_blank(BLANK.into()));
+        for _ in 0..steps {
+            assert_eq!(machine.step(), None);
+        }
+        trim_blanks(machine.get_tape(), SmolStr::from(BLANK))
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn alternate() {
+        assert_eq!(tape_after(ALTERNATE, 8), "0 1 0 1");
+    }
+
+    #[test]
+    fn chains_keep_their_names() {
+        assert_eq!(tape_after(COMPACT, 10), "0 1 0 1");
+        let prog = read(COMPACT)
+            .expect("Valid table.")
+            .into_single_track()
+            .expect("Single track.");
+        let first = prog.respond(&SmolStr::from("b"), &SmolStr::from("0"));
+        assert_eq!(first.goto, Goto::Run(SmolStr::from("b:0:1")));
+        let second = prog.respond(&SmolStr::from("b:0:1"), &SmolStr::from(BLANK));
+        assert_eq!(second.goto, Goto::Run(SmolStr::from("b:0:2")));
+        assert_eq!(second.mv, Some(Movement::Right));
+        let third = prog.respond(&SmolStr::from("b:0:2"), &SmolStr::from(BLANK));
+        assert_eq!(
+            (third.goto, third.write.as_str()),
+            (Goto::Run(SmolStr::from("b")), "1")
+        );
+    }
+
+    #[test]
+    fn any_and_erase() {
+        let text = "
+q | Any  | E, R | q
+  | None | P1   | done
+done | x | | done
+";
+        let prog = read(text).expect("Valid table.");
+        let prog = prog.into_single_track().expect("Single track.");
+        let erase = prog.respond(&SmolStr::from("q"), &SmolStr::from("1"));
+        assert_eq!(
+            (erase.write.as_str(), erase.mv),
+            (BLANK, Some(Movement::Right))
+        );
+        let x = prog.respond(&SmolStr::from("q"), &SmolStr::from("x"));
+        assert_eq!(x.write, BLANK);
+    }
+
+    #[test]
+    fn any_matches_every_symbol_but_the_blank() {
+        let run = |text: &str| {
+            let prog = read(text).expect("Valid table.").into_single_track();
+            let input = Unbounded::from_input(vec!["a".into(), "b".into()], BLANK.into());
+            let mut machine = TuringMachine::new(prog.expect("Single track."), input);
+            let accepted = machine.run();
+            let tape = trim_blanks(machine.get_tape(), SmolStr::from(BLANK));
+            (tape.map(String::from).collect::<String>(), accepted)
+        };
+        // The table never mentions a or b, and done rejects on the blank it reaches.
+        let text = "
+q | Any | E, R | q
+  | None |     | done
+done | x |     | done
+";
+        assert_eq!(run(text), (String::new(), false));
+        // Without a row for None, a blank square rejects.
+        assert_eq!(run("q | Any | E, R | q\n"), (String::new(), false));
+        // A row for a particular symbol takes precedence, and an empty symbol covers the blank.
+        let text = "
+q | Any | E, R | q
+  | b   | R    | q
+  |     | P1   | accept
+";
+        assert_eq!(run(text), ("b1".to_owned(), true));
+    }
+
+    #[test]
+    fn empty_symbol_keeps_apart_from_star() {
+        let text = "
+b | *  | R, R | b
+  |    | R, R | b
+";
+        let prog = read(text).expect("Valid table.");
+        let prog = prog.into_single_track().expect("Single track.");
+        let star = prog.respond(&SmolStr::from("b"), &SmolStr::from("*"));
+        assert_eq!(star.goto, Goto::Run(SmolStr::from("b:*:1")));
+        let other = prog.respond(&SmolStr::from("b"), &SmolStr::from("0"));
+        assert_eq!(other.goto, Goto::Run(SmolStr::from("b:|:1")));
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let err = read("b | None | P0 | c\n\n  | None | R | b\n").unwrap_err();
+        assert!(err.to_string().starts_with("Line 3:"), "{}", err);
+        let err = read("b | None | P0, X | c\n").unwrap_err();
+        assert!(err.to_string().starts_with("Line 1:"), "{}", err);
+        let err = read("// comment\nb | None | P0\n").unwrap_err();
+        assert!(err.to_string().starts_with("Line 2:"), "{}", err);
+        let err = read("b | None | P0 | c\nc | None | R | b:0:1\n").unwrap_err();
+        assert!(err.to_string().starts_with("Line 2:"), "{}", err);
+    }
+}
@@ -1,14 +1,17 @@
 use crate::{
+    multitrack::Tracks,
     program::{Goto, Response, TransitionFn},
     tape::Tape,
 };
-use std::{fmt, fmt::Debug, marker::PhantomData};
+use std::{borrow::Cow, collections::VecDeque, fmt, fmt::Debug, marker::PhantomData};
 
 #[derive(Debug)]
 pub struct TuringMachine<State, Alphabet, TapeImpl, Program> {
     state: Goto<State>,
     prog: Program,
     tape: TapeImpl,
+    output: Vec<Alphabet>,
+    emitted: VecDeque<Vec<Alphabet>>,
     phantom: PhantomData<Alphabet>,
 }
 
@@ -18,11 +21,17 @@ where
     TapeImpl: Tape<Alphabet>,
     Program: TransitionFn<State, Alphabet>,
 {
-    pub fn new(start: State, prog: Program, input: TapeImpl) -> Self {
+    /// Starts in the program's initial state, with the R/W head at the start of the input.
+    pub fn new(prog: Program, input: TapeImpl) -> Self
+    where
+        State: Clone,
+    {
         Self {
-            state: Goto::Run(start),
+            state: Goto::Run(prog.initial().clone()),
             prog,
             tape: input,
+            output: Vec::new(),
+            emitted: VecDeque::new(),
             phantom: PhantomData,
         }
     }
@@ -40,10 +49,29 @@ where
     where
         State: Debug,
         Alphabet: Debug,
+    {
+        self.run_debug_with(|m| m.to_string())
+    }
+
+    /// Like `run_debug`, but with a custom rendering of each step.
+    pub fn run_debug_with<F>(&mut self, view: F) -> Result<bool, std::io::Error>
+    where
+        F: Fn(&Self) -> String,
+    {
+        self.run_checked(|m| {
+            println!("{}", view(m));
+            std::io::stdin().read_line(&mut String::new()).map(drop)
+        })
+    }
+
+    /// Runs until the machine halts, or until `check`, which is called before each step,
+    /// returns an error.
+    pub fn run_checked<F, E>(&mut self, mut check: F) -> Result<bool, E>
+    where
+        F: FnMut(&Self) -> Result<(), E>,
     {
         loop {
-            println!("{}", self);
-            std::io::stdin().read_line(&mut String::new())?;
+            check(self)?;
             if let Some(accept) = self.step() {
                 return Ok(accept);
             }
@@ -55,8 +83,8 @@ where
     pub fn step(&mut self) -> Option<bool> {
         match self.state {
             Goto::Halt(accept) => Some(accept),
-            Goto::Run(ref state) => {
-                let response = (self.prog)(state, self.tape.get());
+            Goto::Run(ref state) | Goto::Emit(ref state) => {
+                let response = self.prog.respond(state, self.tape.get());
                 self.apply_response(response);
                 None
             }
@@ -67,10 +95,27 @@ where
         self.tape.get_all()
     }
 
+    /// Everything emitted to the write-only output tape so far.
+    pub fn get_output(&self) -> &[Alphabet] {
+        &self.output
+    }
+
+    /// Pops the oldest word printed by an enumerator that hasn't been taken yet.
+    pub fn take_emitted(&mut self) -> Option<Vec<Alphabet>> {
+        self.emitted.pop_front()
+    }
+
     fn apply_response(&mut self, response: Response<State, Alphabet>) {
         self.state = response.goto;
         *self.tape.get_mut() = response.write;
-        response.mv.map(|mv| self.tape.move_head(mv));
+        if let Some(mv) = response.mv {
+            self.tape.move_head(mv);
+        }
+        self.output.extend(response.output);
+        if let Goto::Emit(_) = self.state {
+            let word = std::mem::take(&mut self.output);
+            self.emitted.push_back(word);
+        }
     }
 }
 
@@ -91,9 +136,74 @@ where
             if i == head_idx {
                 writeln!(f, "\t\t{:?}", self.state)?;
             } else {
-                writeln!(f, "")?;
+                writeln!(f)?;
             }
         }
+        if !self.output.is_empty() {
+            writeln!(f, "output: {:?}", self.output)?;
+        }
+        writeln!(f, "================")?;
+        Ok(())
+    }
+}
+
+impl<State, Alphabet, TapeImpl, Program> TuringMachine<State, Tracks<Alphabet>, TapeImpl, Program>
+where
+    Alphabet: Clone,
+    TapeImpl: Tape<Tracks<Alphabet>>,
+    Program: TransitionFn<State, Tracks<Alphabet>>,
+{
+    /// Displays the tape horizontally with one row per track.
+    pub fn tracks(&self) -> TrackView<'_, State, Alphabet, TapeImpl, Program> {
+        TrackView(self)
+    }
+}
+
+pub struct TrackView<'a, State, Alphabet, TapeImpl, Program>(
+    &'a TuringMachine<State, Tracks<Alphabet>, TapeImpl, Program>,
+);
+
+impl<State, Alphabet, TapeImpl, Program> fmt::Display
+    for TrackView<'_, State, Alphabet, TapeImpl, Program>
+where
+    State: Debug,
+    Alphabet: Clone + Debug,
+    TapeImpl: Tape<Tracks<Alphabet>>,
+    Program: TransitionFn<State, Tracks<Alphabet>>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (head_idx, items) = self.0.tape.get_radius(8);
+        let cells = items.map(Cow::into_owned).collect::<Vec<_>>();
+        let track_count = cells.iter().map(Tracks::len).max().unwrap_or(0).max(1);
+        let columns = cells
+            .iter()
+            .map(|cell| {
+                (0..track_count)
+                    .map(|t| format!("{:?}", cell.track(t)))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let widths = columns
+            .iter()
+            .map(|col| col.iter().map(|s| s.chars().count()).max().unwrap_or(0))
+            .collect::<Vec<_>>();
+
+        writeln!(f, "================")?;
+        for t in 0..track_count {
+            let row = columns
+                .iter()
+                .zip(&widths)
+                .map(|(col, &width)| format!("{:<width$}", col[t], width = width))
+                .collect::<Vec<_>>()
+                .join(" ");
+            writeln!(f, "{}", row.trim_end())?;
+        }
+        let offset = widths.iter().take(head_idx).map(|w| w + 1).sum::<usize>();
+        writeln!(f, "{:offset$}^ {:?}", "", self.0.state, offset = offset)?;
+        if !self.0.output.is_empty() {
+            let output = self.0.output.iter().map(|cell| cell.track(0));
+            writeln!(f, "output: {:?}", output.collect::<Vec<_>>())?;
+        }
         writeln!(f, "================")?;
         Ok(())
     }
@@ -110,8 +220,8 @@ mod tests {
 
     #[test]
     fn construct() {
-        let prog = ProgramBuilder::<bool, u8>::new().build();
-        let m = TuringMachine::new(false, prog, Unbounded::new());
+        let prog = ProgramBuilder::<bool, u8>::new().build(false);
+        let m = TuringMachine::new(prog, Unbounded::new());
         assert_eq!(m.tape.get(), &0);
     }
 
@@ -127,23 +237,18 @@ mod tests {
             Odd,
         }
 
-        #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+        #[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq)]
         enum Alphabet {
+            #[default]
             Blank,
             Zero,
             One,
         }
 
-        impl Default for Alphabet {
-            fn default() -> Self {
-                Alphabet::Blank
-            }
-        }
-
         use Alphabet::*;
         use State::*;
 
-        fn get_prog() -> impl Fn(&State, &Alphabet) -> Response<State, Alphabet> {
+        fn get_prog() -> impl TransitionFn<State, Alphabet> {
             ProgramBuilder::new()
                 // Scan to the end of the string uselessly.
                 .with_transition((Scan, Blank), (Goto::Run(Flip), Blank, Some(Left)))
@@ -160,13 +265,13 @@ mod tests {
                 .with_transition((Odd, Blank), (Goto::Halt(false), Blank, None))
                 .with_transition((Odd, Zero), (Goto::Run(Odd), Zero, Some(Right)))
                 .with_transition((Odd, One), (Goto::Run(Even), One, Some(Right)))
-                .build()
+                .build(Scan)
         }
 
         #[test]
         fn accept_empty() {
             let prog = get_prog();
-            let mut m = TuringMachine::new(State::Scan, prog, Unbounded::new());
+            let mut m = TuringMachine::new(prog, Unbounded::new());
             assert!(m.run());
         }
 
@@ -175,7 +280,7 @@ mod tests {
             use Alphabet::*;
             let prog = get_prog();
             let tape = vec![Zero, Zero, One, Zero, One, One, Zero];
-            let mut m = TuringMachine::new(State::Scan, prog, Unbounded::from(tape));
+            let mut m = TuringMachine::new(prog, Unbounded::from(tape));
             assert!(m.run())
         }
 
@@ -184,7 +289,7 @@ mod tests {
             use Alphabet::*;
             let prog = get_prog();
             let tape = vec![Zero, Zero, One, One, One, One, Zero];
-            let mut m = TuringMachine::new(State::Scan, prog, Unbounded::from(tape));
+            let mut m = TuringMachine::new(prog, Unbounded::from(tape));
             assert!(!m.run())
         }
     }
@@ -193,50 +298,41 @@ mod tests {
     mod ones_twos_threes {
         use super::*;
         type State = [usize; 3];
-        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        #[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
         enum Alphabet {
+            #[default]
             Blank,
             One,
             Two,
             Three,
         }
 
-        impl Default for Alphabet {
-            fn default() -> Self {
-                Alphabet::Blank
-            }
-        }
-
         // This program technically violates the finite nature of turing machines, but if you're being even more
         // technical, it doesn't.
         fn get_prog() -> impl Fn(&State, &Alphabet) -> Response<State, Alphabet> {
             use Alphabet::*;
             |count, read| {
-                let mut new_count = count.clone();
+                let mut new_count = *count;
                 match read {
                     Blank => {
-                        return Response {
-                            goto: Goto::Halt(count[0] == count[1] && count[1] == count[2]),
-                            write: read.clone(),
-                            mv: None,
-                        }
+                        return Response::new(
+                            Goto::Halt(count[0] == count[1] && count[1] == count[2]),
+                            read.clone(),
+                            None,
+                        )
                     }
                     One => new_count[0] += 1,
                     Two => new_count[1] += 1,
                     Three => new_count[2] += 1,
                 }
-                Response {
-                    goto: Goto::Run(new_count),
-                    write: read.clone(),
-                    mv: Some(Movement::Right),
-                }
+                Response::new(Goto::Run(new_count), read.clone(), Some(Movement::Right))
             }
         }
 
         #[test]
         fn accept_empty() {
             let prog = get_prog();
-            let mut m = TuringMachine::new([0; 3], prog, Unbounded::new());
+            let mut m = TuringMachine::new(([0; 3], prog), Unbounded::new());
             assert!(m.run());
         }
 
@@ -246,145 +342,193 @@ mod tests {
 
             let prog = get_prog();
             let input = vec![One, Three, Two];
-            let mut m = TuringMachine::new([0; 3], prog, Unbounded::from(input));
+            let mut m = TuringMachine::new(([0; 3], prog), Unbounded::from(input));
             assert!(m.run());
         }
 
         #[test]
         fn accept_large() {
-            use std::iter::repeat;
+            use std::iter::repeat_n;
             use Alphabet::*;
 
             let prog = get_prog();
-            let ones = repeat(One).take(300);
-            let twos = repeat(Two).take(300);
-            let threes = repeat(Three).take(300);
+            let ones = repeat_n(One, 300);
+            let twos = repeat_n(Two, 300);
+            let threes = repeat_n(Three, 300);
             let input = ones.chain(twos).chain(threes).collect::<Vec<_>>();
-            let mut m = TuringMachine::new([0; 3], prog, Unbounded::from(input));
+            let mut m = TuringMachine::new(([0; 3], prog), Unbounded::from(input));
             assert!(m.run());
         }
 
         #[test]
         fn reject_no_ones() {
-            use std::iter::repeat;
+            use std::iter::repeat_n;
             use Alphabet::*;
 
             let prog = get_prog();
-            let twos = repeat(Two).take(300);
-            let threes = repeat(Three).take(300);
+            let twos = repeat_n(Two, 300);
+            let threes = repeat_n(Three, 300);
             let input = twos.chain(threes).collect::<Vec<_>>();
-            let mut m = TuringMachine::new([0; 3], prog, Unbounded::from(input));
+            let mut m = TuringMachine::new(([0; 3], prog), Unbounded::from(input));
             assert!(!m.run());
         }
 
         #[test]
         fn reject_no_twos() {
-            use std::iter::repeat;
+            use std::iter::repeat_n;
             use Alphabet::*;
 
             let prog = get_prog();
-            let ones = repeat(One).take(300);
-            let threes = repeat(Three).take(300);
+            let ones = repeat_n(One, 300);
+            let threes = repeat_n(Three, 300);
             let input = ones.chain(threes).collect::<Vec<_>>();
-            let mut m = TuringMachine::new([0; 3], prog, Unbounded::from(input));
+            let mut m = TuringMachine::new(([0; 3], prog), Unbounded::from(input));
             assert!(!m.run());
         }
 
         #[test]
         fn reject_no_threes() {
-            use std::iter::repeat;
+            use std::iter::repeat_n;
             use Alphabet::*;
 
             let prog = get_prog();
-            let twos = repeat(Two).take(300);
-            let ones = repeat(One).take(300);
+            let twos = repeat_n(Two, 300);
+            let ones = repeat_n(One, 300);
             let input = ones.chain(twos).collect::<Vec<_>>();
-            let mut m = TuringMachine::new([0; 3], prog, Unbounded::from(input));
+            let mut m = TuringMachine::new(([0; 3], prog), Unbounded::from(input));
             assert!(!m.run());
         }
 
         #[test]
         fn reject_too_many_ones() {
-            use std::iter::repeat;
+            use std::iter::repeat_n;
             use Alphabet::*;
 
             let prog = get_prog();
-            let ones = repeat(One).take(301);
-            let twos = repeat(Two).take(300);
-            let threes = repeat(Three).take(300);
+            let ones = repeat_n(One, 301);
+            let twos = repeat_n(Two, 300);
+            let threes = repeat_n(Three, 300);
             let input = ones.chain(twos).chain(threes).collect::<Vec<_>>();
-            let mut m = TuringMachine::new([0; 3], prog, Unbounded::from(input));
+            let mut m = TuringMachine::new(([0; 3], prog), Unbounded::from(input));
             assert!(!m.run());
         }
 
         #[test]
         fn reject_too_many_twos() {
-            use std::iter::repeat;
+            use std::iter::repeat_n;
             use Alphabet::*;
 
             let prog = get_prog();
-            let ones = repeat(One).take(300);
-            let twos = repeat(Two).take(301);
-            let threes = repeat(Three).take(300);
+            let ones = repeat_n(One, 300);
+            let twos = repeat_n(Two, 301);
+            let threes = repeat_n(Three, 300);
             let input = ones.chain(twos).chain(threes).collect::<Vec<_>>();
-            let mut m = TuringMachine::new([0; 3], prog, Unbounded::from(input));
+            let mut m = TuringMachine::new(([0; 3], prog), Unbounded::from(input));
             assert!(!m.run());
         }
 
         #[test]
         fn reject_too_many_threes() {
-            use std::iter::repeat;
+            use std::iter::repeat_n;
             use Alphabet::*;
 
             let prog = get_prog();
-            let ones = repeat(One).take(300);
-            let twos = repeat(Two).take(300);
-            let threes = repeat(Three).take(301);
+            let ones = repeat_n(One, 300);
+            let twos = repeat_n(Two, 300);
+            let threes = repeat_n(Three, 301);
             let input = ones.chain(twos).chain(threes).collect::<Vec<_>>();
-            let mut m = TuringMachine::new([0; 3], prog, Unbounded::from(input));
+            let mut m = TuringMachine::new(([0; 3], prog), Unbounded::from(input));
             assert!(!m.run());
         }
 
         #[test]
         fn reject_no_enough_ones() {
-            use std::iter::repeat;
+            use std::iter::repeat_n;
             use Alphabet::*;
 
             let prog = get_prog();
-            let ones = repeat(One).take(299);
-            let twos = repeat(Two).take(300);
-            let threes = repeat(Three).take(300);
+            let ones = repeat_n(One, 299);
+            let twos = repeat_n(Two, 300);
+            let threes = repeat_n(Three, 300);
             let input = ones.chain(twos).chain(threes).collect::<Vec<_>>();
-            let mut m = TuringMachine::new([0; 3], prog, Unbounded::from(input));
+            let mut m = TuringMachine::new(([0; 3], prog), Unbounded::from(input));
             assert!(!m.run());
         }
 
         #[test]
         fn reject_no_enough_twos() {
-            use std::iter::repeat;
+            use std::iter::repeat_n;
             use Alphabet::*;
 
             let prog = get_prog();
-            let ones = repeat(One).take(300);
-            let twos = repeat(Two).take(299);
-            let threes = repeat(Three).take(300);
+            let ones = repeat_n(One, 300);
+            let twos = repeat_n(Two, 299);
+            let threes = repeat_n(Three, 300);
             let input = ones.chain(twos).chain(threes).collect::<Vec<_>>();
-            let mut m = TuringMachine::new([0; 3], prog, Unbounded::from(input));
+            let mut m = TuringMachine::new(([0; 3], prog), Unbounded::from(input));
             assert!(!m.run());
         }
 
         #[test]
         fn reject_no_enough_threes() {
-            use std::iter::repeat;
+            use std::iter::repeat_n;
             use Alphabet::*;
 
             let prog = get_prog();
-            let ones = repeat(One).take(300);
-            let twos = repeat(Two).take(300);
-            let threes = repeat(Three).take(299);
+            let ones = repeat_n(One, 300);
+            let twos = repeat_n(Two, 300);
+            let threes = repeat_n(Three, 299);
             let input = ones.chain(twos).chain(threes).collect::<Vec<_>>();
-            let mut m = TuringMachine::new([0; 3], prog, Unbounded::from(input));
+            let mut m = TuringMachine::new(([0; 3], prog), Unbounded::from(input));
             assert!(!m.run());
         }
     }
+
+    // A transducer that doubles a unary number onto the output tape, leaving the work tape alone.
+    mod transducer {
+        use super::*;
+
+        #[test]
+        fn double() {
+            let prog = ProgramBuilder::new()
+                .with_transition((0, 1), (Goto::Run(1), 1, None, Some(1)))
+                .with_transition((1, 1), (Goto::Run(0), 1, Some(Right), Some(1)))
+                .with_transition((0, 0), (Goto::Halt(true), 0, None))
+                .build(0);
+            let mut m = TuringMachine::new(prog, Unbounded::from(vec![1, 1, 1]));
+            assert!(m.run());
+            assert_eq!(m.get_output(), &[1; 6]);
+            assert_eq!(m.get_tape().collect::<Vec<_>>(), vec![1, 1, 1, 0]);
+        }
+    }
+
+    // An enumerator for the language 1*: it prints "", "1", "11", ... forever.
+    mod enumerator {
+        use super::*;
+
+        #[test]
+        fn ones() {
+            // The state is the length of the word being printed and how much of it is done.
+            let prog = |&(len, done): &(usize, usize), read: &u8| Response {
+                goto: if done < len {
+                    Goto::Run((len, done + 1))
+                } else {
+                    Goto::Emit((len + 1, 0))
+                },
+                write: *read,
+                mv: None,
+                output: if done < len { Some(1) } else { None },
+            };
+            let mut m = TuringMachine::new(((0, 0), prog), Unbounded::new());
+            for len in 0..5 {
+                let word = loop {
+                    assert_eq!(m.step(), None);
+                    if let Some(word) = m.take_emitted() {
+                        break word;
+                    }
+                };
+                assert_eq!(word, vec![1; len]);
+            }
+        }
+    }
 }
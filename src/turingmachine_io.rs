@@ -0,0 +1,373 @@
+use serde_yaml::Value;
+
+use std::{error::Error, fmt};
+
+use crate::{
+    program::Movement,
+    program_ron::{ProgramFile, Rule},
+};
+
+/// A YAML file that isn't a turingmachine.io program, or a program it can't express.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TuringMachineIoError(String);
+
+/// Reads a turingmachine.io program.
+///
+/// turingmachine.io machines only halt, when a state has no instruction for the symbol read.
+/// Accepting and rejecting are this tool's extension: a state left empty (`done:`) accepts,
+/// and one given an empty mapping (`done: {}`) rejects, as does a missing instruction.
+/// turingmachine.io runs either kind of file, and just halts in those states.
+pub fn read(yaml: &str) -> Result<ProgramFile, TuringMachineIoError> {
+    let doc: Value = serde_yaml::from_str(yaml)?;
+    let field = |name: &str| {
+        doc.get(name)
+            .ok_or_else(|| TuringMachineIoError(format!("The program has no {:?}.", name)))
+    };
+    let blank = scalar(field("blank")?)?;
+    let start = scalar(field("start state")?)?;
+    let table = field("table")?
+        .as_mapping()
+        .ok_or_else(|| TuringMachineIoError("The table must map states to instructions.".into()))?;
+
+    let mut prog = ProgramFile::new(start, blank);
+    let mut accepting = Vec::new();
+    let mut rejecting = Vec::new();
+    for (state, instructions) in table {
+        let state = scalar(state)?;
+        let instructions = match instructions {
+            Value::Null => {
+                accepting.push(state);
+                continue;
+            }
+            Value::Mapping(instructions) if instructions.is_empty() => {
+                rejecting.push(state);
+                continue;
+            }
+            Value::Mapping(instructions) => instructions,
+            _ => {
+                return Err(TuringMachineIoError(format!(
+                    "The instructions for {:?} must be a mapping.",
+                    state
+                )))
+            }
+        };
+        for (symbols, action) in instructions {
+            let symbols = match symbols {
+                Value::Sequence(symbols) => symbols.iter().map(scalar).collect::<Result<_, _>>()?,
+                symbol => vec![scalar(symbol)?],
+            };
+            for read in symbols {
+                let (goto, write, mv) = expand(&state, &read, action)?;
+                prog.add_rule(Rule {
+                    state: state.clone(),
+                    read,
+                    goto,
+                    write,
+                    mv,
+                })?;
+            }
+        }
+    }
+    prog.set_halting(accepting, rejecting);
+    Ok(prog)
+}
+
+/// Whether YAML looks like a turingmachine.io program rather than some other schema.
+pub fn is_turingmachine_io(yaml: &str) -> bool {
+    serde_yaml::from_str::<Value>(yaml)
+        .map(|doc| doc.get("table").is_some() && doc.get("start state").is_some())
+        .unwrap_or(false)
+}
+
+/// Spells out an action: `R`, `L`, `{R: next}` or `{write: x, L: next}`, where a missing
+/// next state means staying in the same one.
+fn expand(
+    state: &str,
+    read: &str,
+    action: &Value,
+) -> Result<(String, String, Option<Movement>), TuringMachineIoError> {
+    let invalid = || {
+        TuringMachineIoError(format!(
+            "Can't make sense of the instruction for {:?} reading {:?}.",
+            state, read
+        ))
+    };
+    let direction = |s: &str| match s {
+        "L" => Ok(Some(Movement::Left)),
+        "R" => Ok(Some(Movement::Right)),
+        _ => Err(invalid()),
+    };
+    match action {
+        Value::String(s) => Ok((state.to_owned(), read.to_owned(), direction(s)?)),
+        Value::Mapping(action) => {
+            let mut goto = state.to_owned();
+            let mut write = read.to_owned();
+            let mut mv = None;
+            for (key, value) in action {
+                match key.as_str().ok_or_else(invalid)? {
+                    "write" => write = scalar(value)?,
+                    key => {
+                        if mv.is_some() {
+                            return Err(invalid());
+                        }
+                        mv = direction(key)?;
+                        if !value.is_null() {
+                            goto = scalar(value)?;
+                        }
+                    }
+                }
+            }
+            Ok((goto, write, mv))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// Symbols and state names may be written as numbers or booleans as well as strings.
+fn scalar(value: &Value) -> Result<String, TuringMachineIoError> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        _ => Err(TuringMachineIoError(format!(
+            "Expected a symbol or state name, found {:?}.",
+            value
+        ))),
+    }
+}
+
+/// Writes a program for turingmachine.io. Accepting states are left empty, and rejecting
+/// states reached by a transition get an empty mapping, so either way the machine takes the
+/// transition before halting there. Stay moves go right and come back through an extra state.
+pub fn write(prog: &ProgramFile) -> Result<String, TuringMachineIoError> {
+    let rules = prog.rules()?;
+    let blank = prog.blank().to_string();
+    let rejects = |state: &str| prog.reject_states().iter().any(|s| s == state);
+
+    let mut alphabet = vec![blank.clone()];
+    let mut states: Vec<&str> = vec![prog.initial()];
+    let mut accepting = Vec::new();
+    let mut rejecting = Vec::new();
+    for rule in &rules {
+        for symbol in &[&rule.read, &rule.write] {
+            if !alphabet.contains(symbol) {
+                alphabet.push(symbol.to_string());
+            }
+        }
+        if !states.contains(&rule.state.as_str()) {
+            states.push(&rule.state);
+        }
+        if prog.accept_states().contains(&rule.goto) && !accepting.contains(&&rule.goto) {
+            accepting.push(&rule.goto);
+        }
+        if rejects(&rule.goto) && !rejecting.contains(&&rule.goto) {
+            rejecting.push(&rule.goto);
+        }
+    }
+
+    let mut yaml = format!(
+        "blank: {}\nstart state: {}\ntable:\n",
+        quote(&blank),
+        quote(prog.initial())
+    );
+    let mut returns = Vec::new();
+    for state in states {
+        // Symbols with the same instruction share a line.
+        let mut lines: Vec<(Vec<&str>, String)> = Vec::new();
+        for rule in rules.iter().filter(|rule| rule.state == state) {
+            let (mv, goto) = match &rule.mv {
+                Some(Movement::Left) => ("L", rule.goto.clone()),
+                Some(Movement::Right) => ("R", rule.goto.clone()),
+                None => {
+                    if !returns.contains(&rule.goto) {
+                        returns.push(rule.goto.clone());
+                    }
+                    ("R", stay(&rule.goto))
+                }
+            };
+            let instruction = match (rule.write == rule.read, goto == state) {
+                (true, true) => mv.to_owned(),
+                (true, false) => format!("{{{}: {}}}", mv, quote(&goto)),
+                (false, true) => format!("{{write: {}, {}}}", quote(&rule.write), mv),
+                (false, false) => format!(
+                    "{{write: {}, {}: {}}}",
+                    quote(&rule.write),
+                    mv,
+                    quote(&goto)
+                ),
+            };
+            match lines.iter_mut().find(|(_, other)| *other == instruction) {
+                Some((symbols, _)) => symbols.push(&rule.read),
+                None => lines.push((vec![&rule.read], instruction)),
+            }
+        }
+        if lines.is_empty() {
+            // Left empty, the state would accept. Without instructions it rejects.
+            yaml.push_str(&format!("  {}: {{}}\n", quote(state)));
+        } else {
+            yaml.push_str(&format!("  {}:\n", quote(state)));
+        }
+        for (symbols, instruction) in lines {
+            yaml.push_str(&format!("    {}: {}\n", symbol_key(&symbols), instruction));
+        }
+    }
+    for goto in returns {
+        yaml.push_str(&format!("  {}:\n", quote(&stay(&goto))));
+        let symbols = alphabet.iter().map(String::as_str).collect::<Vec<_>>();
+        yaml.push_str(&format!(
+            "    {}: {{L: {}}}\n",
+            symbol_key(&symbols),
+            quote(&goto)
+        ));
+    }
+    for state in accepting {
+        yaml.push_str(&format!("  {}:\n", quote(state)));
+    }
+    for state in rejecting {
+        yaml.push_str(&format!("  {}: {{}}\n", quote(state)));
+    }
+    Ok(yaml)
+}
+
+/// The extra state a stay move into `goto` passes through.
+fn stay(goto: &str) -> String {
+    format!("{} (stay)", goto)
+}
+
+fn symbol_key(symbols: &[&str]) -> String {
+    match symbols {
+        [symbol] => quote(symbol),
+        symbols => {
+            let symbols = symbols.iter().map(|s| quote(s)).collect::<Vec<_>>();
+            format!("[{}]", symbols.join(", "))
+        }
+    }
+}
+
+/// Single-quoted YAML, which only needs quotes doubled.
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+impl fmt::Display for TuringMachineIoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for TuringMachineIoError {}
+
+impl From<serde_yaml::Error> for TuringMachineIoError {
+    fn from(e: serde_yaml::Error) -> Self {
+        TuringMachineIoError(format!("Malformed YAML: {}", e))
+    }
+}
+
+impl From<ron::de::Error> for TuringMachineIoError {
+    fn from(e: ron::de::Error) -> Self {
+        TuringMachineIoError(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program::{Goto, TransitionFn};
+    use smol_str::SmolStr;
+
+    // The binary increment example from turingmachine.io.
+    const INCREMENT: &str = "
+input: '1011'
+blank: ' '
+start state: right
+table:
+  # scan to the rightmost digit
+  right:
+    [1,0]: R
+    ' '  : {L: carry}
+  # then carry the 1
+  carry:
+    1      : {write: 0, L}
+    [0,' ']: {write: 1, L: done}
+  done:
+";
+
+    #[test]
+    fn import() {
+        let prog = read(INCREMENT).expect("Valid program.");
+        assert_eq!(prog.initial(), "right");
+        assert_eq!(prog.blank(), " ");
+        assert_eq!(prog.accept_states(), &["done".to_string()]);
+
+        let table = prog.into_single_track().expect("Single track.");
+        let respond =
+            |state: &str, read: &str| table.respond(&SmolStr::from(state), &SmolStr::from(read));
+        let right = respond("right", "0");
+        assert_eq!(right.goto, Goto::Run(SmolStr::from("right")));
+        assert_eq!(
+            (right.write.as_str(), right.mv),
+            ("0", Some(Movement::Right))
+        );
+        assert_eq!(
+            respond("right", " ").goto,
+            Goto::Run(SmolStr::from("carry"))
+        );
+        let carry = respond("carry", "1");
+        assert_eq!(carry.goto, Goto::Run(SmolStr::from("carry")));
+        assert_eq!(
+            (carry.write.as_str(), carry.mv),
+            ("0", Some(Movement::Left))
+        );
+        assert_eq!(respond("carry", " ").goto, Goto::Halt(true));
+        assert_eq!(respond("carry", " ").write, "1");
+    }
+
+    #[test]
+    fn round_trip() {
+        let prog = read(INCREMENT).expect("Valid program.");
+        let yaml = write(&prog).expect("Exportable.");
+        let again = read(&yaml).expect("Exports a valid program.");
+        assert_eq!(again.rules(), prog.rules());
+        assert_eq!(again.accept_states(), prog.accept_states());
+    }
+
+    #[test]
+    fn stay_moves() {
+        let mut prog = ProgramFile::new("a", "_");
+        prog.add_rule(Rule {
+            state: "a".into(),
+            read: "x".into(),
+            goto: "b".into(),
+            write: "y".into(),
+            mv: None,
+        })
+        .expect("First transition.");
+        let yaml = write(&prog).expect("Exportable.");
+        let again = read(&yaml).expect("Exports a valid program.");
+        let table = again.into_single_track().expect("Single track.");
+        let first = table.respond(&SmolStr::from("a"), &SmolStr::from("x"));
+        assert_eq!(first.goto, Goto::Run(SmolStr::from("b (stay)")));
+        assert_eq!(first.mv, Some(Movement::Right));
+        let back = table.respond(&SmolStr::from("b (stay)"), &SmolStr::from("_"));
+        assert_eq!(back.goto, Goto::Run(SmolStr::from("b")));
+        assert_eq!(back.mv, Some(Movement::Left));
+    }
+
+    #[test]
+    fn rejecting_keeps_the_last_step() {
+        let mut prog = ProgramFile::new("a", "_");
+        prog.add_rule(Rule {
+            state: "a".into(),
+            read: "x".into(),
+            goto: "reject".into(),
+            write: "y".into(),
+            mv: Some(Movement::Left),
+        })
+        .expect("First transition.");
+        let yaml = write(&prog).expect("Exportable.");
+        assert!(yaml.contains("  'reject': {}\n"), "{}", yaml);
+        let again = read(&yaml).expect("Exports a valid program.");
+        assert_eq!(again.rules(), prog.rules());
+        assert_eq!(again.reject_states(), &["reject".to_string()]);
+    }
+}
@@ -0,0 +1,29 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+/// Runs `tm` with the given arguments and input, returning what it printed.
+fn tm(args: &[&str], input: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_tm"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("The binary runs.");
+    child
+        .stdin
+        .take()
+        .expect("Piped.")
+        .write_all(input.as_bytes())
+        .expect("Input is written.");
+    let output = child.wait_with_output().expect("The binary finishes.");
+    String::from_utf8(output.stdout).expect("Output is UTF-8.")
+}
+
+#[test]
+fn prints_input_the_machine_never_reached() {
+    // w#w rejects at the first mismatch, long before reading the end of its input.
+    assert_eq!(tm(&["run", "examples/w#w.ron"], "01#10"), "x1#10false\n");
+    assert_eq!(tm(&["run", "examples/flip.ron"], "abab"), "babatrue\n");
+}